@@ -1,35 +1,101 @@
 extern crate failure;
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::env;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 
 use failure::Error;
 
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Repeat {
+    frequency: i64,
+    // Changes applied in total before the frequency repeated.
+    steps: u64,
+    // The 0-based pass through the list, and the index of the change within it, that got there.
+    pass: u64,
+    position: usize,
+}
+
+// After the first pass, every pass just adds the drift (the sum of all the changes) to the
+// frequencies of the one before. So a frequency f reached at step j of a pass comes back around
+// to an earlier f' only if they're in the same residue class mod the drift, with f' ahead of f
+// in the drift's direction; the nearest such f' is the first one it meets, and the answer is
+// whichever meeting happens soonest.
+fn first_repeat(changes: &[i64]) -> Option<Repeat> {
+    let n = changes.len() as u64;
+    if n == 0 {
+        return None;
+    }
+
+    // sums[i] is the frequency after the first i changes of the first pass.
+    let mut sums = Vec::with_capacity(changes.len());
+    let mut drift = 0;
+    for change in changes {
+        sums.push(drift);
+        drift += change;
+    }
+
+    let repeat_after = |steps: u64| Repeat {
+        frequency: sums[(steps % n) as usize] + (steps / n) as i64 * drift,
+        steps,
+        pass: (steps - 1) / n,
+        position: ((steps - 1) % n) as usize,
+    };
+
+    let mut seen = HashSet::new();
+    for (i, &frequency) in sums.iter().enumerate() {
+        if !seen.insert(frequency) {
+            return Some(repeat_after(i as u64));
+        }
+    }
+    if drift == 0 {
+        return Some(repeat_after(n));
+    }
+
+    let mut classes: HashMap<i64, Vec<usize>> = HashMap::new();
+    for (i, &frequency) in sums.iter().enumerate() {
+        classes
+            .entry(frequency.rem_euclid(drift.abs()))
+            .or_default()
+            .push(i);
+    }
+
+    let mut first = None;
+    for class in classes.values_mut() {
+        class.sort_by_key(|&i| sums[i] * drift.signum());
+        for pair in class.windows(2) {
+            let (behind, ahead) = (pair[0], pair[1]);
+            let passes = ((sums[ahead] - sums[behind]) / drift) as u64;
+            let steps = passes * n + behind as u64;
+            first = Some(first.map_or(steps, |first: u64| first.min(steps)));
+        }
+    }
+
+    first.map(repeat_after)
+}
+
 fn main() -> Result<(), Error> {
     let args: Vec<String> = env::args().collect();
     let file = File::open(&args[1])?;
 
-    let changes: Vec<i32> = BufReader::new(file)
+    let changes: Vec<i64> = BufReader::new(file)
         .lines()
         .map(|l| l.expect("file read failed"))
-        .map(|l| l.parse::<i32>().expect("not-a-number"))
+        .map(|l| l.parse::<i64>().expect("not-a-number"))
         .collect();
 
-    println!("{}", changes.iter().sum::<i32>());
+    println!("{}", changes.iter().sum::<i64>());
 
-    let mut seen = HashSet::new();
-    seen.insert(0i32);
-
-    let mut frequency = 0;
-    for change in changes.iter().cycle() {
-        frequency += change;
-        if seen.contains(&frequency) {
-            println!("first duplicate: {}", frequency);
-            break;
-        }
-        seen.insert(frequency);
+    match first_repeat(&changes) {
+        Some(repeat) => println!(
+            "first duplicate: {} (after {} changes, at change {} of pass {})",
+            repeat.frequency,
+            repeat.steps,
+            repeat.position + 1,
+            repeat.pass + 1
+        ),
+        None => println!("frequency never repeats"),
     }
 
     Ok(())
@@ -35,10 +35,6 @@ impl Rect {
     fn height(&self) -> i32 {
         self.bottom - self.top + 1
     }
-
-    fn area(&self) -> i64 {
-        self.width() as i64 * self.height() as i64
-    }
 }
 
 fn propagate(input: &Vec<Point>, steps: i32) -> Vec<Point> {
@@ -54,6 +50,87 @@ fn propagate(input: &Vec<Point>, steps: i32) -> Vec<Point> {
         .collect()
 }
 
+// Width and height of the bounding box after `t` seconds, without building the moved points.
+fn extent(points: &[Point], t: i64) -> (i64, i64) {
+    let (mut left, mut right) = (i64::MAX, i64::MIN);
+    let (mut top, mut bottom) = (i64::MAX, i64::MIN);
+    for p in points.iter() {
+        let x = p.position.0 as i64 + t * p.velocity.0 as i64;
+        let y = p.position.1 as i64 + t * p.velocity.1 as i64;
+        left = std::cmp::min(left, x);
+        right = std::cmp::max(right, x);
+        top = std::cmp::min(top, y);
+        bottom = std::cmp::max(bottom, y);
+    }
+    (right - left + 1, bottom - top + 1)
+}
+
+// Integer minimizer of a convex function over t >= 0: double out to bracket the minimum, then
+// ternary search inside the bracket.
+fn convex_argmin<F: Fn(i64) -> i64>(f: F) -> i64 {
+    let mut hi = 1;
+    while f(2 * hi) < f(hi) {
+        hi *= 2;
+    }
+
+    let (mut lo, mut hi) = (0, 2 * hi);
+    while hi - lo > 2 {
+        let m1 = lo + (hi - lo) / 3;
+        let m2 = hi - (hi - lo) / 3;
+        if f(m1) < f(m2) {
+            hi = m2;
+        } else {
+            lo = m1;
+        }
+    }
+    (lo..=hi).min_by_key(|&t| f(t)).unwrap()
+}
+
+// The extent along each axis is the gap between the largest and smallest of a set of linear
+// functions of time, so it's convex. Before both axes bottom out the area is shrinking and after
+// both it's growing, so every local minimum of the area lies between the two; scan just that
+// stretch and return the best `count` of them. A flat run of equal areas counts once, at its
+// start.
+fn convergence_times(points: &[Point], count: usize) -> Vec<(i64, i64)> {
+    let tx = convex_argmin(|t| extent(points, t).0);
+    let ty = convex_argmin(|t| extent(points, t).1);
+
+    let start = std::cmp::min(tx, ty);
+    let end = std::cmp::max(tx, ty);
+    let area = |t: i64| {
+        let (width, height) = extent(points, t);
+        width * height
+    };
+
+    // Areas from one before the stretch to one after it, with nothing before t = 0.
+    let areas: Vec<Option<i64>> = (start - 1..=end + 1)
+        .map(|t| if t < 0 { None } else { Some(area(t)) })
+        .collect();
+    let mut candidates: Vec<(i64, i64)> = areas
+        .windows(3)
+        .zip(start..)
+        .filter_map(|(w, t)| {
+            let here = w[1]?;
+            let rising_before = match w[0] {
+                Some(before) => before <= here,
+                None => false,
+            };
+            let falling_after = match w[2] {
+                Some(after) => after < here,
+                None => false,
+            };
+            if !rising_before && !falling_after {
+                Some((t, here))
+            } else {
+                None
+            }
+        })
+        .collect();
+    candidates.sort_by_key(|&(t, area)| (area, t));
+    candidates.truncate(count);
+    candidates
+}
+
 fn bounds(pattern: &Vec<Point>) -> Rect {
     pattern.iter().fold(Rect::new(), |acc, p| Rect {
         left: std::cmp::min(acc.left, p.position.0),
@@ -63,22 +140,207 @@ fn bounds(pattern: &Vec<Point>) -> Rect {
     })
 }
 
-fn draw(pattern: &Vec<Point>) {
+fn render(pattern: &Vec<Point>) -> Vec<Vec<bool>> {
     let rect = bounds(pattern);
-    let mut grid = vec![vec![' '; rect.width() as usize]; rect.height() as usize];
+    let mut grid = vec![vec![false; rect.width() as usize]; rect.height() as usize];
     pattern.iter().for_each(|p| {
         let x = (p.position.0 - rect.left) as usize;
         let y = (p.position.1 - rect.top) as usize;
-        grid[y][x] = '#';
+        grid[y][x] = true;
     });
+    grid
+}
+
+fn draw(grid: &[Vec<bool>]) {
     for row in grid.iter() {
-        for c in row.iter() {
-            print!("{}", c);
+        for &lit in row.iter() {
+            print!("{}", if lit { '#' } else { ' ' });
         }
         println!("");
     }
 }
 
+// Letter shapes used by Advent puzzles that spell out their answers, with blank columns
+// trimmed off so that glyphs of any width can be cut apart on the empty columns between them.
+const FONT_6: &[(char, &[&str])] = &[
+    ('A', &[".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+    ('B', &["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+    ('C', &[".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+    ('E', &["####", "#...", "###.", "#...", "#...", "####"]),
+    ('F', &["####", "#...", "###.", "#...", "#...", "#..."]),
+    ('G', &[".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+    ('H', &["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+    ('I', &["###", ".#.", ".#.", ".#.", ".#.", "###"]),
+    ('J', &["..##", "...#", "...#", "...#", "#..#", ".##."]),
+    ('K', &["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+    ('L', &["#...", "#...", "#...", "#...", "#...", "####"]),
+    ('O', &[".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('P', &["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+    ('R', &["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+    ('S', &[".###", "#...", "#...", ".##.", "...#", "###."]),
+    ('U', &["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('Y', &["#...#", "#...#", ".#.#.", "..#..", "..#..", "..#.."]),
+    ('Z', &["####", "...#", "..#.", ".#..", "#...", "####"]),
+];
+
+const FONT_10: &[(char, &[&str])] = &[
+    (
+        'A',
+        &[
+            "..##..", ".#..#.", "#....#", "#....#", "#....#", "######", "#....#", "#....#",
+            "#....#", "#....#",
+        ],
+    ),
+    (
+        'B',
+        &[
+            "#####.", "#....#", "#....#", "#....#", "#####.", "#....#", "#....#", "#....#",
+            "#....#", "#####.",
+        ],
+    ),
+    (
+        'C',
+        &[
+            ".####.", "#....#", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....",
+            "#....#", ".####.",
+        ],
+    ),
+    (
+        'E',
+        &[
+            "######", "#.....", "#.....", "#.....", "#####.", "#.....", "#.....", "#.....",
+            "#.....", "######",
+        ],
+    ),
+    (
+        'F',
+        &[
+            "######", "#.....", "#.....", "#.....", "#####.", "#.....", "#.....", "#.....",
+            "#.....", "#.....",
+        ],
+    ),
+    (
+        'G',
+        &[
+            ".####.", "#....#", "#.....", "#.....", "#.....", "#..###", "#....#", "#....#",
+            "#...##", ".###.#",
+        ],
+    ),
+    (
+        'H',
+        &[
+            "#....#", "#....#", "#....#", "#....#", "######", "#....#", "#....#", "#....#",
+            "#....#", "#....#",
+        ],
+    ),
+    (
+        'J',
+        &[
+            "...###", "....#.", "....#.", "....#.", "....#.", "....#.", "....#.", "#...#.",
+            "#...#.", ".###..",
+        ],
+    ),
+    (
+        'K',
+        &[
+            "#....#", "#...#.", "#..#..", "#.#...", "##....", "##....", "#.#...", "#..#..",
+            "#...#.", "#....#",
+        ],
+    ),
+    (
+        'L',
+        &[
+            "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....",
+            "#.....", "######",
+        ],
+    ),
+    (
+        'N',
+        &[
+            "#....#", "##...#", "##...#", "#.#..#", "#.#..#", "#..#.#", "#..#.#", "#...##",
+            "#...##", "#....#",
+        ],
+    ),
+    (
+        'P',
+        &[
+            "#####.", "#....#", "#....#", "#....#", "#####.", "#.....", "#.....", "#.....",
+            "#.....", "#.....",
+        ],
+    ),
+    (
+        'R',
+        &[
+            "#####.", "#....#", "#....#", "#....#", "#####.", "#..#..", "#...#.", "#...#.",
+            "#....#", "#....#",
+        ],
+    ),
+    (
+        'X',
+        &[
+            "#....#", "#....#", ".#..#.", ".#..#.", "..##..", "..##..", ".#..#.", ".#..#.",
+            "#....#", "#....#",
+        ],
+    ),
+    (
+        'Z',
+        &[
+            "######", ".....#", ".....#", "....#.", "...#..", "..#...", ".#....", "#.....",
+            "#.....", "######",
+        ],
+    ),
+];
+
+fn ocr(grid: &[Vec<bool>]) -> Result<String, Error> {
+    let font = match grid.len() {
+        6 => FONT_6,
+        10 => FONT_10,
+        height => return Err(format_err!("no font for {}-row letters", height)),
+    };
+
+    let width = grid[0].len();
+    let blank = |x: usize| grid.iter().all(|row| !row[x]);
+
+    let mut message = String::new();
+    let mut x = 0;
+    while x < width {
+        if blank(x) {
+            x += 1;
+            continue;
+        }
+
+        let start = x;
+        while x < width && !blank(x) {
+            x += 1;
+        }
+
+        let glyph: Vec<String> = grid
+            .iter()
+            .map(|row| {
+                row[start..x]
+                    .iter()
+                    .map(|&lit| if lit { '#' } else { '.' })
+                    .collect()
+            })
+            .collect();
+
+        let letter = font
+            .iter()
+            .find(|(_, rows)| rows.iter().eq(glyph.iter()))
+            .map(|&(letter, _)| letter)
+            .ok_or_else(|| {
+                format_err!(
+                    "unrecognized glyph at column {}:\n{}",
+                    start,
+                    glyph.join("\n")
+                )
+            })?;
+        message.push(letter);
+    }
+
+    Ok(message)
+}
+
 fn main() -> Result<(), Error> {
     let args: Vec<String> = env::args().collect();
     let file = File::open(&args[1])?;
@@ -99,16 +361,16 @@ fn main() -> Result<(), Error> {
         .map(|res: Result<Point, Error>| res.expect("failed to parse point"))
         .collect();
 
-    let mut score = bounds(&points).area();
-    for n in 1.. {
-        let trial = bounds(&propagate(&points, n)).area();
-        if trial > score {
-            println!("found pattern at {} seconds", n - 1);
-            draw(&propagate(&points, n - 1));
-            break;
-        }
-        score = trial;
+    let candidates = convergence_times(&points, 3);
+    for &(t, area) in candidates.iter().skip(1) {
+        println!("runner-up: {} seconds (area {})", t, area);
     }
 
+    let (best, _) = candidates[0];
+    println!("found pattern at {} seconds", best);
+    let grid = render(&propagate(&points, best as i32));
+    draw(&grid);
+    println!("message: {}", ocr(&grid)?);
+
     Ok(())
 }
@@ -1,62 +1,162 @@
-use failure::Error;
+use std::cmp::Reverse;
+use std::env;
+use std::fs::File;
+use std::io::prelude::*;
+
+use failure::{format_err, Error};
 use rayon::prelude::*;
 
-fn calc_power(x: i32, y: i32) -> i32 {
+fn calc_power(x: i32, y: i32, serial: i32) -> i32 {
     let rack_id = x + 10;
-    let serial = 2866;
     ((rack_id * y + serial) * rack_id / 100) % 10 - 5
 }
 
-fn main() -> Result<(), Error> {
-    let (best, best_x, best_y, best_l) = (1..301)
-        .into_par_iter()
-        .map(|l| {
-            let mut best = -1;
-            let mut best_x = 0;
-            let mut best_y = 0;
-
-            // Initialize the window for (1,1).
-            let mut start_power = 0;
-            for x in 1..=l {
-                for y in 1..=l {
-                    start_power += calc_power(x, y);
-                }
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Square {
+    x: usize,
+    y: usize,
+    size: usize,
+    power: i32,
+}
+
+impl Square {
+    // Most power first; ties go to whichever square comes first in reading order, then the
+    // smaller square.
+    fn rank(&self) -> (Reverse<i32>, usize, usize, usize) {
+        (Reverse(self.power), self.y, self.x, self.size)
+    }
+}
+
+fn keep_top(top: &mut Vec<Square>, square: Square, n: usize) {
+    if n == 0 || (top.len() == n && square.rank() >= top[n - 1].rank()) {
+        return;
+    }
+
+    let at = top
+        .binary_search_by_key(&square.rank(), Square::rank)
+        .unwrap_or_else(|i| i);
+    top.insert(at, square);
+    top.truncate(n);
+}
+
+struct PowerGrid {
+    width: usize,
+    height: usize,
+    // Summed-area table with a row and column of zeroes on the top and left edges, so that
+    // sums[y * (width + 1) + x] is the total power of every cell up to and including (x, y).
+    sums: Vec<i32>,
+}
+
+impl PowerGrid {
+    fn new(serial: i32, width: usize, height: usize) -> PowerGrid {
+        let stride = width + 1;
+        let mut sums = vec![0; stride * (height + 1)];
+        for y in 1..=height {
+            for x in 1..=width {
+                sums[y * stride + x] = calc_power(x as i32, y as i32, serial)
+                    + sums[(y - 1) * stride + x]
+                    + sums[y * stride + x - 1]
+                    - sums[(y - 1) * stride + x - 1];
             }
+        }
+
+        PowerGrid {
+            width,
+            height,
+            sums,
+        }
+    }
 
-            // Now scan with a sliding window...
-            for x in 1..=(300 - l) {
-                // Slide the window along the y axis, checking each window for improvement.
-                let mut power = start_power;
-                for y in 1..=(300 - l) {
-                    if power > best {
-                        best = power;
-                        best_x = x;
-                        best_y = y;
-                    }
-
-                    // Moving down a row, so subtract the top row's power and add the next.
-                    for offset in 0..l {
-                        power -= calc_power(x + offset, y);
-                        power += calc_power(x + offset, y + l);
-                    }
-                }
-
-                // Now slide the start_power right by a column, and repeat.
-                for y in 1..=l {
-                    start_power -= calc_power(x, y);
-                    start_power += calc_power(x + l, y);
-                }
+    // Total power of the size x size square whose top-left cell is (x, y), 1-based like the
+    // puzzle's coordinates.
+    fn square_power(&self, x: usize, y: usize, size: usize) -> i32 {
+        let stride = self.width + 1;
+        let (left, top) = (x - 1, y - 1);
+        let (right, bottom) = (left + size, top + size);
+        self.sums[bottom * stride + right]
+            - self.sums[top * stride + right]
+            - self.sums[bottom * stride + left]
+            + self.sums[top * stride + left]
+    }
+
+    fn top_squares_of_size(&self, size: usize, n: usize) -> Vec<Square> {
+        let mut top = Vec::with_capacity(n + 1);
+        if size == 0 || size > self.width || size > self.height {
+            return top;
+        }
+
+        for y in 1..=(self.height - size + 1) {
+            for x in 1..=(self.width - size + 1) {
+                let power = self.square_power(x, y, size);
+                keep_top(&mut top, Square { x, y, size, power }, n);
             }
+        }
+        top
+    }
+
+    fn top_squares(&self, min_size: usize, max_size: usize, n: usize) -> Vec<Square> {
+        let candidates: Vec<Square> = (min_size..max_size + 1)
+            .into_par_iter()
+            .flat_map(|size| self.top_squares_of_size(size, n))
+            .collect();
+
+        let mut top = Vec::with_capacity(n + 1);
+        for square in candidates {
+            keep_top(&mut top, square, n);
+        }
+        top
+    }
+
+    fn best_square(&self, size: usize) -> Option<Square> {
+        self.top_squares_of_size(size, 1).pop()
+    }
+
+    fn best_square_any_size(&self) -> Option<Square> {
+        let max_size = std::cmp::min(self.width, self.height);
+        self.top_squares(1, max_size, 1).pop()
+    }
+}
+
+fn main() -> Result<(), Error> {
+    let args: Vec<String> = env::args().collect();
+    let mut file = File::open(&args[1])?;
+    let mut input = String::new();
+    file.read_to_string(&mut input)?;
+
+    // The input is the grid serial number, optionally followed by the side of the grid.
+    let mut tokens = input.split_whitespace();
+    let serial: i32 = tokens
+        .next()
+        .ok_or_else(|| format_err!("missing serial number"))?
+        .parse()?;
+    let side: usize = tokens.next().map_or(Ok(300), |s| s.parse())?;
+
+    let grid = PowerGrid::new(serial, side, side);
+
+    match grid.best_square(3) {
+        Some(best) => println!("best 3x3 power ({}) @ {},{}", best.power, best.x, best.y),
+        None => println!("grid too small for a 3x3 square"),
+    }
 
-            (best, best_x, best_y, l)
-        })
-        .max_by_key(|t| t.0)
-        .unwrap();
+    for (i, square) in grid.top_squares(1, side, 3).iter().enumerate() {
+        println!(
+            "#{}: power {} is {}x{} @ {},{},{}",
+            i + 1,
+            square.power,
+            square.size,
+            square.size,
+            square.x,
+            square.y,
+            square.size
+        );
+    }
 
-    println!(
-        "best power ({}) is {}x{} @ {},{}",
-        best, best_l, best_l, best_x, best_y
-    );
+    if let Some(best) = grid.best_square_any_size() {
+        println!(
+            "best power ({}) is {}x{} @ {},{}",
+            best.power, best.size, best.size, best.x, best.y
+        );
+    }
 
     Ok(())
 }
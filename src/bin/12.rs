@@ -1,120 +1,216 @@
-use std::collections::HashMap;
-
-use failure::Error;
-
-fn tick(input: &str) -> char {
-    match input {
-        "..#.." => '.',
-        "..#.#" => '.',
-        "#.#.." => '.',
-        ".#..#" => '.',
-        "#...." => '.',
-        "....#" => '.',
-        ".#.#." => '#',
-        "#.###" => '.',
-        "####." => '.',
-        "....." => '.',
-        ".#..." => '#',
-        "#####" => '#',
-        ".####" => '.',
-        "#..#." => '#',
-        "#...#" => '#',
-        ".###." => '.',
-        "###.#" => '#',
-        "...##" => '#',
-        "#.##." => '#',
-        ".#.##" => '#',
-        "##.#." => '#',
-        "...#." => '.',
-        "..###" => '#',
-        "###.." => '#',
-        "##..." => '.',
-        "..##." => '.',
-        ".##.#" => '.',
-        "##.##" => '.',
-        ".##.." => '.',
-        "##..#" => '#',
-        "#.#.#" => '.',
-        "#..##" => '#',
-        _ => panic!("got input {} but have no matching rule", input),
+use std::env;
+use std::fmt;
+use std::fs::File;
+use std::io::prelude::*;
+
+use advent2018::cycle::find_cycle;
+use failure::{bail, format_err, Error};
+
+fn is_plant(c: char) -> Result<bool, Error> {
+    match c {
+        '#' => Ok(true),
+        '.' => Ok(false),
+        _ => Err(format_err!("bad pot: {}", c)),
     }
 }
 
-fn render(state: &HashMap<i32, char>, left: i32, right: i32) {
-    println!(
-        "[{:02}] {}",
-        left,
-        (left..=right)
-            .map(|p| state.get(&p).or(Some(&'.')).unwrap())
-            .collect::<String>()
-    );
+// Lookup table from a 5-pot neighborhood, leftmost pot in the high bit, to the next state of
+// the middle pot.
+struct Rules([bool; 32]);
+
+impl Rules {
+    fn parse<'a, I: Iterator<Item = &'a str>>(lines: I) -> Result<Rules, Error> {
+        let mut rules = [false; 32];
+        for line in lines {
+            let mut parts = line.split(" => ");
+            let (pattern, result) = match (parts.next(), parts.next()) {
+                (Some(pattern), Some(result)) if pattern.len() == 5 && result.len() == 1 => {
+                    (pattern, result)
+                }
+                _ => bail!("bad rule: {}", line),
+            };
+
+            let mut index = 0;
+            for c in pattern.chars() {
+                index = (index << 1) | is_plant(c)? as usize;
+            }
+            rules[index] = is_plant(result.chars().next().unwrap())?;
+        }
+
+        if rules[0] {
+            bail!("rule ..... => # would fill the infinite row with plants");
+        }
+
+        Ok(Rules(rules))
+    }
+}
+
+// A row of pots packed 64 to a word, trimmed so that it starts and ends with a plant.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Pattern {
+    len: usize,
+    words: Vec<u64>,
 }
 
-fn step(state: &mut HashMap<i32, char>, left: &mut i32, right: &mut i32) {
-    *left -= 5;
-    *right += 5;
+impl Pattern {
+    // Returns the number of empty pots skipped at the start along with the trimmed pattern.
+    fn from_pots<I: Iterator<Item = bool>>(pots: I) -> (usize, Pattern) {
+        let mut pattern = Pattern {
+            len: 0,
+            words: Vec::new(),
+        };
 
-    let mut next_state = HashMap::new();
-    for pos in *left..=*right {
-        let pattern: String = (pos - 2..=pos + 2)
-            .map(|p| state.get(&p).or(Some(&'.')).unwrap())
-            .collect();
-        next_state.insert(pos, tick(&pattern));
+        let mut skipped = 0;
+        for pot in pots {
+            if pattern.len == 0 && !pot {
+                skipped += 1;
+            } else {
+                pattern.push(pot);
+            }
+        }
+
+        while pattern.len > 0 && !pattern.get(pattern.len - 1) {
+            pattern.len -= 1;
+        }
+        pattern.words.truncate(pattern.len.div_ceil(64));
+
+        (skipped, pattern)
     }
 
-    *state = next_state;
-    while let Some(&'.') = state.get(&left).or(Some(&'.')) {
-        *left += 1;
+    fn push(&mut self, pot: bool) {
+        if self.len.is_multiple_of(64) {
+            self.words.push(0);
+        }
+        if pot {
+            self.words[self.len / 64] |= 1 << (self.len % 64);
+        }
+        self.len += 1;
     }
-    while let Some(&'.') = state.get(&right).or(Some(&'.')) {
-        *right -= 1;
+
+    fn get(&self, i: usize) -> bool {
+        i < self.len && (self.words[i / 64] >> (i % 64)) & 1 == 1
     }
 }
 
-fn main() -> Result<(), Error> {
-    let initial_state = "##.#..#.#..#.####.#########.#...#.#.#......##.#.#...##.....#...#...#.##.#...##...#.####.##..#.#..#.";
+#[derive(Debug, Clone)]
+struct Pots {
+    // Position of the first pot in the pattern.
+    offset: i64,
+    pattern: Pattern,
+}
 
-    let mut state = HashMap::new();
-    initial_state.chars().enumerate().for_each(|(pos, c)| {
-        state.insert(pos as i32, c);
-    });
+impl Pots {
+    fn parse(s: &str) -> Result<Pots, Error> {
+        let pots = s.chars().map(is_plant).collect::<Result<Vec<bool>, _>>()?;
+        let (skipped, pattern) = Pattern::from_pots(pots.into_iter());
+        Ok(Pots {
+            offset: skipped as i64,
+            pattern,
+        })
+    }
 
-    let mut left = 0i32;
-    let mut right = initial_state.len() as i32;
+    fn step(&self, rules: &Rules) -> Pots {
+        // Output pot j sits at offset - 2 + j, so its neighborhood is input pots j - 4 ..= j;
+        // shift each input pot into the bottom of the window as we go.
+        let mut window = 0;
+        let pots = (0..self.pattern.len + 4).map(|j| {
+            window = ((window << 1) | self.pattern.get(j) as usize) & 0b11111;
+            rules.0[window]
+        });
 
-    for gen in 0..20 {
-        print!("{:03}: ", gen);
-        render(&state, left, right);
-        step(&mut state, &mut left, &mut right);
+        let (skipped, pattern) = Pattern::from_pots(pots);
+        Pots {
+            offset: self.offset - 2 + skipped as i64,
+            pattern,
+        }
     }
 
-    let first_answer: i32 = (left..=right)
-        .filter_map(|p| {
-            if let Some(&'#') = state.get(&p) {
-                return Some(p);
-            }
-            None
-        })
-        .sum();
-    println!("first answer: {}", first_answer);
+    fn sum(&self) -> i64 {
+        (0..self.pattern.len)
+            .filter(|&i| self.pattern.get(i))
+            .map(|i| self.offset + i as i64)
+            .sum()
+    }
+}
 
-    for gen in 20..112 {
-        print!("{:03}: ", gen);
-        render(&state, left, right);
-        step(&mut state, &mut left, &mut right);
+impl fmt::Display for Pots {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{:02}] ", self.offset)?;
+        for i in 0..self.pattern.len {
+            f.write_str(if self.pattern.get(i) { "#" } else { "." })?;
+        }
+        Ok(())
     }
+}
 
-    // At this point the pattern is fixed, but it slides right 1 pot/step.
-    let offset = 50000000000i64 - 112;
-    let second_answer: i64 = (left..=right)
-        .filter_map(|p| {
-            if let Some(&'#') = state.get(&p) {
-                return Some(p as i64 + offset);
-            }
-            None
-        })
-        .sum();
-    println!("second answer: {}", second_answer);
+#[derive(Debug, Copy, Clone)]
+struct Glider {
+    start: u64,
+    period: u64,
+    shift: i64,
+}
+
+fn advance(mut pots: Pots, rules: &Rules, generations: u64) -> Pots {
+    for _ in 0..generations {
+        pots = pots.step(rules);
+    }
+    pots
+}
+
+// Steps the pots forward, watching for a generation whose pattern is a (possibly shifted) copy
+// of an earlier one; from then on every `period` generations just slide the row by `shift`
+// pots, so whole periods can be skipped in one go.
+fn pots_after(pots: Pots, rules: &Rules, generations: u64) -> (Pots, Option<Glider>) {
+    let cycle = find_cycle(
+        &pots,
+        generations,
+        |p| p.step(rules),
+        |a, b| a.pattern == b.pattern,
+    );
+    let cycle = match cycle {
+        Some(cycle) => cycle,
+        None => return (advance(pots, rules, generations), None),
+    };
+
+    let first = advance(pots, rules, cycle.start);
+    let glider = Glider {
+        start: cycle.start,
+        period: cycle.period,
+        shift: advance(first.clone(), rules, cycle.period).offset - first.offset,
+    };
+
+    let remaining = generations - glider.start;
+    let mut pots = advance(first, rules, remaining % glider.period);
+    pots.offset += (remaining / glider.period) as i64 * glider.shift;
+    (pots, Some(glider))
+}
+
+fn main() -> Result<(), Error> {
+    let args: Vec<String> = env::args().collect();
+    let mut file = File::open(&args[1])?;
+    let mut input = String::new();
+    file.read_to_string(&mut input)?;
+
+    let mut lines = input.lines();
+    let initial_state = lines
+        .next()
+        .and_then(|l| l.trim().split("initial state: ").nth(1))
+        .ok_or_else(|| format_err!("missing initial state"))?;
+    let pots = Pots::parse(initial_state)?;
+    let rules = Rules::parse(lines.map(|l| l.trim()).filter(|l| !l.is_empty()))?;
+
+    let (after_20, _) = pots_after(pots.clone(), &rules, 20);
+    println!("{}", after_20);
+    println!("first answer: {}", after_20.sum());
+
+    let (later, glider) = pots_after(pots, &rules, 50_000_000_000);
+    if let Some(glider) = glider {
+        println!(
+            "pattern repeats every {} generations from generation {}, moving {} pots each time",
+            glider.period, glider.start, glider.shift
+        );
+    }
+    println!("second answer: {}", later.sum());
 
     Ok(())
 }
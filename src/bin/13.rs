@@ -172,10 +172,20 @@ impl Cart {
     }
 }
 
+#[derive(Debug, Clone)]
+struct Collision {
+    tick: usize,
+    x: usize,
+    y: usize,
+    carts: Vec<Cart>,
+}
+
 #[derive(Debug, Clone)]
 struct State {
     track: Track,
     carts: Vec<Cart>,
+    ticks: usize,
+    collisions: Vec<Collision>,
 }
 
 impl fmt::Display for State {
@@ -222,35 +232,78 @@ impl State {
         State {
             track: Track::new(&input),
             carts,
+            ticks: 0,
+            collisions: Vec::new(),
         }
     }
 
-    fn tick(mut self) -> State {
+    fn tick(&mut self) -> &[Collision] {
+        self.ticks += 1;
+        let first_new = self.collisions.len();
+
         // Enforce the movement order dictated by the problem.
         self.carts
             .sort_unstable_by_key(|c| (-(c.y as i32), -(c.x as i32)));
 
         // O(n^2) but for a small number of carts...
-        let mut carts = Vec::new();
+        let mut carts: Vec<Cart> = Vec::new();
         while let Some(cart) = self.carts.pop() {
             let cart = cart.traverse(&self.track);
+            let (x, y) = (cart.x, cart.y);
 
-            let before = carts.len() + self.carts.len();
-            self.carts.retain(|c: &Cart| c.x != cart.x || c.y != cart.y);
-            carts.retain(|c: &Cart| c.x != cart.x || c.y != cart.y);
-            let after = carts.len() + self.carts.len();
+            let (mut wrecked, waiting): (Vec<Cart>, Vec<Cart>) =
+                self.carts.drain(..).partition(|c| c.x == x && c.y == y);
+            let (moved, rest): (Vec<Cart>, Vec<Cart>) =
+                carts.drain(..).partition(|c| c.x == x && c.y == y);
+            self.carts = waiting;
+            carts = rest;
+            wrecked.extend(moved);
 
-            if after < before {
-                println!("collision at {},{}", cart.x, cart.y);
-            } else {
+            if wrecked.is_empty() {
                 carts.push(cart);
+            } else {
+                wrecked.insert(0, cart);
+                self.collisions.push(Collision {
+                    tick: self.ticks,
+                    x,
+                    y,
+                    carts: wrecked,
+                });
             }
         }
 
-        State { carts, ..self }
+        self.carts = carts;
+        &self.collisions[first_new..]
+    }
+
+    fn run_until_first_crash(&mut self, max_ticks: usize) -> Option<&Collision> {
+        for _ in 0..max_ticks {
+            if !self.collisions.is_empty() {
+                break;
+            }
+            self.tick();
+        }
+        self.collisions.first()
+    }
+
+    fn run_until_one_left(&mut self, max_ticks: usize) -> Option<Cart> {
+        for _ in 0..max_ticks {
+            if self.carts.len() <= 1 {
+                break;
+            }
+            self.tick();
+        }
+
+        if self.carts.len() == 1 {
+            Some(self.carts[0])
+        } else {
+            None
+        }
     }
 }
 
+const MAX_TICKS: usize = 1_000_000;
+
 fn main() -> Result<(), Error> {
     let args: Vec<String> = env::args().collect();
     let mut file = File::open(&args[1])?;
@@ -258,16 +311,35 @@ fn main() -> Result<(), Error> {
     file.read_to_string(&mut input)?;
 
     let mut state = State::new(&input);
-    println!("{}", state);
 
-    while state.carts.len() > 1 {
-        state = state.tick();
+    match state.run_until_first_crash(MAX_TICKS) {
+        Some(crash) => println!(
+            "first collision at {},{} on tick {}",
+            crash.x, crash.y, crash.tick
+        ),
+        None => println!("no collision within {} ticks", MAX_TICKS),
     }
 
-    println!("{}", state);
+    match state.run_until_one_left(MAX_TICKS) {
+        Some(survivor) => println!(
+            "The sole cart is at {},{} after tick {}",
+            survivor.x, survivor.y, state.ticks
+        ),
+        None => println!(
+            "{} carts left after {} ticks",
+            state.carts.len(),
+            state.ticks
+        ),
+    }
 
-    if let Some(survivor) = state.carts.first() {
-        println!("The sole cart is at {},{}", survivor.x, survivor.y);
+    for collision in state.collisions.iter() {
+        println!(
+            "tick {}: {} carts collided at {},{}",
+            collision.tick,
+            collision.carts.len(),
+            collision.x,
+            collision.y
+        );
     }
 
     Ok(())
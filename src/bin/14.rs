@@ -1,100 +1,137 @@
-use failure::Error;
-
-fn first_answer() {
-    let mut board: Vec<usize> = vec![3, 7];
-    let mut elf1 = 0;
-    let mut elf2 = 1;
-
-    let mut digits: Vec<usize> = Vec::new();
-
-    let after = 768071;
-    while board.len() < after + 10 {
-        let score1 = board[elf1];
-        let score2 = board[elf2];
-        let mut score = score1 + score2;
-
-        digits.push(score % 10);
-        score /= 10;
-        while score > 0 {
-            digits.push(score % 10);
-            score /= 10;
-        }
-
-        while !digits.is_empty() {
-            board.push(digits.pop().unwrap());
-        }
-
-        let n = board.len();
-        elf1 = (elf1 + 1 + score1) % n;
-        elf2 = (elf2 + 1 + score2) % n;
-    }
-
-    println!("first answer: {:?}", &board[after..after + 10]);
-}
+use std::env;
+use std::fs::File;
+use std::io::prelude::*;
 
-const PATTERN: [usize; 6] = [7, 6, 8, 0, 7, 1];
+use failure::{bail, Error};
 
-struct Match {
-    start: usize,
+// An endless stream of recipe scores, kept one digit per byte so the tens of millions of
+// recipes part two needs stay small.
+struct Scoreboard {
+    recipes: Vec<u8>,
+    elves: (usize, usize),
     next: usize,
 }
 
-impl Match {
-    fn new(start: usize) -> Match {
-        Match { start, next: 0 }
+impl Scoreboard {
+    fn new() -> Scoreboard {
+        Scoreboard {
+            recipes: vec![3, 7],
+            elves: (0, 1),
+            next: 0,
+        }
     }
 
-    fn wants(&self, n: usize) -> bool {
-        n == PATTERN[self.next]
+    fn step(&mut self) {
+        let score1 = self.recipes[self.elves.0];
+        let score2 = self.recipes[self.elves.1];
+        let score = score1 + score2;
+        if score >= 10 {
+            self.recipes.push(score / 10);
+        }
+        self.recipes.push(score % 10);
+
+        let n = self.recipes.len();
+        self.elves.0 = (self.elves.0 + 1 + score1 as usize) % n;
+        self.elves.1 = (self.elves.1 + 1 + score2 as usize) % n;
     }
 }
 
-fn second_answer() {
-    let mut board: Vec<usize> = vec![3, 7];
-    let mut elf1 = 0;
-    let mut elf2 = 1;
+impl Iterator for Scoreboard {
+    type Item = u8;
 
-    let mut digits: Vec<usize> = Vec::new();
-
-    let mut matches: Vec<Match> = Vec::new();
+    fn next(&mut self) -> Option<u8> {
+        while self.next >= self.recipes.len() {
+            self.step();
+        }
+        self.next += 1;
+        Some(self.recipes[self.next - 1])
+    }
+}
 
-    loop {
-        let score1 = board[elf1];
-        let score2 = board[elf2];
-        let mut score = score1 + score2;
+// Knuth-Morris-Pratt matcher fed one digit at a time.
+struct Matcher {
+    pattern: Vec<u8>,
+    // fallback[i] is the length of the longest proper prefix of pattern[..=i] that is also a
+    // suffix of it, i.e. how much of a match survives a mismatch after pattern[i].
+    fallback: Vec<usize>,
+    matched: usize,
+}
 
-        digits.push(score % 10);
-        score /= 10;
-        while score > 0 {
-            digits.push(score % 10);
-            score /= 10;
+impl Matcher {
+    fn new(pattern: &[u8]) -> Matcher {
+        let mut fallback = vec![0; pattern.len()];
+        let mut k = 0;
+        for i in 1..pattern.len() {
+            while k > 0 && pattern[i] != pattern[k] {
+                k = fallback[k - 1];
+            }
+            if pattern[i] == pattern[k] {
+                k += 1;
+            }
+            fallback[i] = k;
         }
 
-        while !digits.is_empty() {
-            matches.push(Match::new(board.len()));
+        Matcher {
+            pattern: pattern.to_vec(),
+            fallback,
+            matched: 0,
+        }
+    }
 
-            let recipe = digits.pop().unwrap();
-            board.push(recipe);
+    // Returns true when `digit` completes an occurrence of the pattern.
+    fn push(&mut self, digit: u8) -> bool {
+        while self.matched > 0 && digit != self.pattern[self.matched] {
+            self.matched = self.fallback[self.matched - 1];
+        }
+        if digit == self.pattern[self.matched] {
+            self.matched += 1;
+        }
 
-            matches.retain(|m| m.wants(recipe));
-            for m in matches.iter_mut() {
-                m.next += 1;
-                if m.next == PATTERN.len() {
-                    println!("second answer: {}", m.start);
-                    return;
-                }
-            }
+        if self.matched == self.pattern.len() {
+            self.matched = self.fallback[self.matched - 1];
+            true
+        } else {
+            false
         }
+    }
+}
+
+fn first_answer(after: usize) -> String {
+    Scoreboard::new()
+        .skip(after)
+        .take(10)
+        .map(|d| (b'0' + d) as char)
+        .collect()
+}
 
-        let n = board.len();
-        elf1 = (elf1 + 1 + score1) % n;
-        elf2 = (elf2 + 1 + score2) % n;
+fn second_answer(pattern: &[u8]) -> usize {
+    if pattern.is_empty() {
+        return 0;
     }
+
+    let mut matcher = Matcher::new(pattern);
+    let end = Scoreboard::new().position(|d| matcher.push(d)).unwrap();
+    end + 1 - pattern.len()
 }
 
 fn main() -> Result<(), Error> {
-    first_answer();
-    second_answer();
+    let args: Vec<String> = env::args().collect();
+    let mut file = File::open(&args[1])?;
+    let mut input = String::new();
+    file.read_to_string(&mut input)?;
+    let input = input.trim();
+
+    // Part two searches for the digits as written, leading zeroes included.
+    let mut pattern = Vec::with_capacity(input.len());
+    for c in input.chars() {
+        match c.to_digit(10) {
+            Some(d) => pattern.push(d as u8),
+            None => bail!("bad input: {}", input),
+        }
+    }
+
+    println!("first answer: {}", first_answer(input.parse()?));
+    println!("second answer: {}", second_answer(&pattern));
 
     Ok(())
 }
@@ -1,146 +1,394 @@
 use std::collections::VecDeque;
 use std::env;
+use std::fmt;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
 
-use failure::Error;
+use failure::{bail, ensure, format_err, Error};
 use regex::Regex;
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 enum Cell {
     Sand,
     Clay,
-    Reachable(bool, bool),
+    // Water the springs can reach, and whether it's found to be walled in on either side: with
+    // clay on the left and the right of a run of water resting on something solid, the run
+    // fills up and stays put.
+    Water { held_left: bool, held_right: bool },
 }
 
+const FLOWING: Cell = Cell::Water {
+    held_left: false,
+    held_right: false,
+};
+
 impl Cell {
+    fn is_water(self) -> bool {
+        self != Cell::Sand && self != Cell::Clay
+    }
+
+    fn is_settled(self) -> bool {
+        self == Cell::Water {
+            held_left: true,
+            held_right: true,
+        }
+    }
+
     fn is_stable(self) -> bool {
-        self == Cell::Clay || self == Cell::Reachable(true, true)
+        self == Cell::Clay || self.is_settled()
+    }
+
+    fn symbol(self) -> char {
+        match self {
+            Cell::Sand => '.',
+            Cell::Clay => '#',
+            _ if self.is_settled() => '~',
+            _ => '|',
+        }
+    }
+
+    fn colour(self) -> [u8; 3] {
+        match self {
+            Cell::Sand => [0xe8, 0xd8, 0xa8],
+            Cell::Clay => [0x80, 0x50, 0x30],
+            _ if self.is_settled() => [0x10, 0x40, 0xc0],
+            _ => [0x70, 0xb0, 0xf0],
+        }
     }
 }
 
-fn main() -> Result<(), Error> {
-    let args: Vec<String> = env::args().collect();
-    let file = File::open(&args[1])?;
+// A straight line of clay, ends included.
+#[derive(Debug, Copy, Clone)]
+struct Vein {
+    x: (i64, i64),
+    y: (i64, i64),
+}
 
-    let y_range_re = Regex::new(r"x=(\d+), y=(\d+)..(\d+)")?;
-    let x_range_re = Regex::new(r"y=(\d+), x=(\d+)..(\d+)")?;
-
-    let mut grid = vec![vec![Cell::Sand; 2048]; 2048];
-    let mut y_min = 2048;
-    let mut y_max = 0;
-
-    for line in BufReader::new(file).lines().map(|l| l.unwrap()) {
-        if let Some(captures) = y_range_re.captures(&line) {
-            let x: usize = captures[1].parse()?;
-            let y_start: usize = captures[2].parse()?;
-            let y_end: usize = captures[3].parse()?;
-            for y in y_start..=y_end {
-                grid[y][x] = Cell::Clay;
-                y_min = y_min.min(y);
-                y_max = y_max.max(y);
-            }
-        } else if let Some(captures) = x_range_re.captures(&line) {
-            let y: usize = captures[1].parse()?;
-            let x_start: usize = captures[2].parse()?;
-            let x_end: usize = captures[3].parse()?;
-            for x in x_start..=x_end {
-                grid[y][x] = Cell::Clay;
-                y_min = y_min.min(y);
-                y_max = y_max.max(y);
+fn parse_veins<R: BufRead>(input: R) -> Result<Vec<Vein>, Error> {
+    let re = Regex::new(r"^([xy])=(-?\d+), ([xy])=(-?\d+)\.\.(-?\d+)$")?;
+
+    let mut veins = Vec::new();
+    for line in input.lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let captures = re
+            .captures(line)
+            .ok_or_else(|| format_err!("bad input line: {}", line))?;
+        let at: i64 = captures[2].parse()?;
+        let (start, end): (i64, i64) = (captures[4].parse()?, captures[5].parse()?);
+        ensure!(start <= end, "backwards range: {}", line);
+        veins.push(match (&captures[1], &captures[3]) {
+            ("x", "y") => Vein {
+                x: (at, at),
+                y: (start, end),
+            },
+            ("y", "x") => Vein {
+                x: (start, end),
+                y: (at, at),
+            },
+            _ => bail!("bad input line: {}", line),
+        });
+    }
+    Ok(veins)
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct WaterCount {
+    settled: usize,
+    flowing: usize,
+}
+
+impl WaterCount {
+    fn total(&self) -> usize {
+        self.settled + self.flowing
+    }
+}
+
+struct Reservoir {
+    // Coordinates of the top-left cell and the size of the grid.
+    left: i64,
+    top: i64,
+    width: usize,
+    height: usize,
+    cells: Vec<Cell>,
+    // Rows from the shallowest to the deepest clay; only water in these counts.
+    clay_top: i64,
+    clay_bottom: i64,
+    springs: Vec<(i64, i64)>,
+}
+
+impl Reservoir {
+    // The grid spans the clay and the springs with a column spare on each side, since water
+    // spilling past the outermost clay falls down the next column over.
+    fn new(veins: &[Vein], springs: &[(i64, i64)]) -> Result<Reservoir, Error> {
+        ensure!(!veins.is_empty(), "no clay");
+        ensure!(!springs.is_empty(), "no springs");
+
+        let xs = veins
+            .iter()
+            .flat_map(|v| vec![v.x.0, v.x.1])
+            .chain(springs.iter().map(|s| s.0));
+        let (left, right) = (xs.clone().min().unwrap() - 1, xs.max().unwrap() + 1);
+        let clay_top = veins.iter().map(|v| v.y.0).min().unwrap();
+        let clay_bottom = veins.iter().map(|v| v.y.1).max().unwrap();
+        let top = springs.iter().map(|s| s.1).min().unwrap().min(clay_top);
+
+        let width = (right - left + 1) as usize;
+        let height = (clay_bottom - top + 1) as usize;
+        let mut reservoir = Reservoir {
+            left,
+            top,
+            width,
+            height,
+            cells: vec![Cell::Sand; width * height],
+            clay_top,
+            clay_bottom,
+            springs: springs.to_vec(),
+        };
+
+        for vein in veins {
+            for y in vein.y.0..=vein.y.1 {
+                for x in vein.x.0..=vein.x.1 {
+                    reservoir.set(x, y, Cell::Clay);
+                }
             }
+        }
+        for &(x, y) in springs {
+            ensure!(
+                reservoir.get(x, y) != Some(Cell::Clay),
+                "spring at {},{} is inside clay",
+                x,
+                y
+            );
+        }
+
+        Ok(reservoir)
+    }
+
+    fn index(&self, x: i64, y: i64) -> Option<usize> {
+        let (col, row) = (x - self.left, y - self.top);
+        if col < 0 || row < 0 || col as usize >= self.width || row as usize >= self.height {
+            None
         } else {
-            panic!("bad input line: {}", line);
+            Some(row as usize * self.width + col as usize)
         }
     }
 
-    // Color the grid with reachable cells.
-    let mut active = VecDeque::new();
-    grid[1][500] = Cell::Reachable(false, false);
-    active.push_back((500, 1));
+    fn get(&self, x: i64, y: i64) -> Option<Cell> {
+        self.index(x, y).map(|i| self.cells[i])
+    }
 
-    while let Some((x, y)) = active.pop_front() {
-        // Don't go outside the designated area.
-        if y > y_max {
-            continue;
+    fn set(&mut self, x: i64, y: i64, cell: Cell) {
+        if let Some(i) = self.index(x, y) {
+            self.cells[i] = cell;
+        }
+    }
+
+    // Wets a sand cell and queues it up to spread further.
+    fn soak(&mut self, x: i64, y: i64, active: &mut VecDeque<(i64, i64)>) {
+        if self.get(x, y) == Some(Cell::Sand) {
+            self.set(x, y, FLOWING);
+            active.push_back((x, y));
         }
+    }
 
-        let below = grid[y + 1][x];
-        if below == Cell::Sand {
-            // Since water could reach *this* spot, it can always go down from here.
-            grid[y + 1][x] = Cell::Reachable(false, false);
-            active.push_back((x, y + 1));
-        } else if below.is_stable() {
-            // Reachability propagates left-right if the space below it is stable.
-            if grid[y][x - 1] == Cell::Sand {
-                grid[y][x - 1] = Cell::Reachable(false, false);
-                active.push_back((x - 1, y));
+    // Queues a water cell up again because something next to it changed.
+    fn revisit(&self, x: i64, y: i64, active: &mut VecDeque<(i64, i64)>) {
+        if let Some(cell) = self.get(x, y) {
+            if cell.is_water() {
+                active.push_back((x, y));
             }
-            if grid[y][x + 1] == Cell::Sand {
-                grid[y][x + 1] = Cell::Reachable(false, false);
-                active.push_back((x + 1, y));
+        }
+    }
+
+    // Spreads the water from every spring as far as it can go.
+    fn fill(&mut self) {
+        let mut active = VecDeque::new();
+        for &(x, y) in self.springs.clone().iter() {
+            self.soak(x, y + 1, &mut active);
+        }
+
+        while let Some((x, y)) = active.pop_front() {
+            // Whatever falls past the deepest clay is gone for good.
+            if y >= self.clay_bottom {
+                continue;
             }
 
-            // Before fully retiring this cell, also evaluate stability.
-            match (grid[y][x - 1], grid[y][x]) {
-                (_, Cell::Reachable(true, _)) => (),
-                (Cell::Reachable(true, _), Cell::Reachable(false, r))
-                | (Cell::Clay, Cell::Reachable(false, r)) => {
-                    // This cell is confirmed bounded left.
-                    // Affects reachable cells above (reachable) or left ( stable).
-                    grid[y][x] = Cell::Reachable(true, r);
-                    if let Cell::Reachable(_, _) = grid[y - 1][x] {
-                        active.push_back((x, y - 1));
+            let below = match self.get(x, y + 1) {
+                Some(below) => below,
+                None => continue,
+            };
+            if below == Cell::Sand {
+                // Since water could reach *this* spot, it can always go down from here.
+                self.soak(x, y + 1, &mut active);
+            } else if below.is_stable() {
+                // Water spreads sideways over anything solid.
+                self.soak(x - 1, y, &mut active);
+                self.soak(x + 1, y, &mut active);
+
+                // Before fully retiring this cell, also see whether it's now walled in; if so,
+                // the water above and beside it may be too.
+                if let Some(Cell::Water {
+                    held_left: false,
+                    held_right,
+                }) = self.get(x, y)
+                {
+                    match self.get(x - 1, y) {
+                        Some(Cell::Clay)
+                        | Some(Cell::Water {
+                            held_left: true, ..
+                        }) => {
+                            self.set(
+                                x,
+                                y,
+                                Cell::Water {
+                                    held_left: true,
+                                    held_right,
+                                },
+                            );
+                            self.revisit(x, y - 1, &mut active);
+                            self.revisit(x + 1, y, &mut active);
+                        }
+                        _ => (),
                     }
-                    if let Cell::Reachable(_, _) = grid[y][x + 1] {
-                        active.push_back((x + 1, y));
+                }
+
+                if let Some(Cell::Water {
+                    held_left,
+                    held_right: false,
+                }) = self.get(x, y)
+                {
+                    match self.get(x + 1, y) {
+                        Some(Cell::Clay)
+                        | Some(Cell::Water {
+                            held_right: true, ..
+                        }) => {
+                            self.set(
+                                x,
+                                y,
+                                Cell::Water {
+                                    held_left,
+                                    held_right: true,
+                                },
+                            );
+                            self.revisit(x, y - 1, &mut active);
+                            self.revisit(x - 1, y, &mut active);
+                        }
+                        _ => (),
                     }
                 }
-                _ => (),
             }
+        }
+    }
 
-            match (grid[y][x], grid[y][x + 1]) {
-                (Cell::Reachable(_, true), _) => (),
-                (Cell::Reachable(l, false), Cell::Reachable(_, true))
-                | (Cell::Reachable(l, false), Cell::Clay) => {
-                    // This cell is confirmed bounded right.
-                    // Affects reachable cells above (reachable) or left ( stable).
-                    grid[y][x] = Cell::Reachable(l, true);
-                    if let Cell::Reachable(_, _) = grid[y - 1][x] {
-                        active.push_back((x, y - 1));
-                    }
-                    if let Cell::Reachable(_, _) = grid[y][x - 1] {
-                        active.push_back((x - 1, y));
-                    }
+    fn water(&self) -> WaterCount {
+        let mut count = WaterCount {
+            settled: 0,
+            flowing: 0,
+        };
+        for y in self.clay_top..=self.clay_bottom {
+            for x in self.left..self.left + self.width as i64 {
+                match self.get(x, y) {
+                    Some(cell) if cell.is_settled() => count.settled += 1,
+                    Some(cell) if cell.is_water() => count.flowing += 1,
+                    _ => (),
                 }
-                _ => (),
             }
         }
+        count
     }
 
-    let answer = (y_min..=y_max)
-        .flat_map(|y| grid[y].iter())
-        .filter(|c| {
-            if let Cell::Reachable(_, _) = c {
-                true
-            } else {
-                false
+    // One pixel per cell of the grid, with the springs in red.
+    fn write_ppm<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        write!(out, "P6\n{} {}\n255\n", self.width, self.height)?;
+
+        let mut pixels: Vec<u8> = self
+            .cells
+            .iter()
+            .flat_map(|c| c.colour().to_vec())
+            .collect();
+        for &(x, y) in self.springs.iter() {
+            if let Some(i) = self.index(x, y) {
+                pixels[3 * i..3 * i + 3].copy_from_slice(&[0xe0, 0x20, 0x20]);
             }
+        }
+        out.write_all(&pixels)
+    }
+}
+
+// The puzzle's picture of the ground, from the springs down to the deepest clay and from just
+// left of the leftmost clay to just right of the rightmost.
+impl fmt::Display for Reservoir {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (row, cells) in self.cells.chunks(self.width).enumerate() {
+            let y = self.top + row as i64;
+            let line: String = cells
+                .iter()
+                .enumerate()
+                .map(|(col, cell)| {
+                    if self.springs.contains(&(self.left + col as i64, y)) {
+                        '+'
+                    } else {
+                        cell.symbol()
+                    }
+                })
+                .collect();
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+fn main() -> Result<(), Error> {
+    let args: Vec<String> = env::args().collect();
+    let file = File::open(&args[1])?;
+
+    // Optional arguments: spring positions as x,y (the puzzle has just the one at 500,0), --map
+    // to print the ground once it's filled, and --ppm <path> to write a picture of it.
+    let map = args[2..].iter().any(|a| a == "--map");
+    let ppm = args[2..]
+        .iter()
+        .position(|a| a == "--ppm")
+        .map(|i| {
+            args.get(i + 3)
+                .ok_or_else(|| format_err!("--ppm needs a path"))
         })
-        .count();
-    println!("first answer: {}", answer);
-
-    let answer = (y_min..=y_max)
-        .flat_map(|y| grid[y].iter())
-        .filter(|c| {
-            if let Cell::Reachable(true, true) = c {
-                true
-            } else {
-                false
+        .transpose()?;
+    let springs = args[2..]
+        .iter()
+        .filter(|a| !a.starts_with("--") && Some(*a) != ppm)
+        .map(|a| {
+            let mut parts = a.split(',').map(|s| s.trim().parse::<i64>());
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some(x), Some(y), None) => Ok((x?, y?)),
+                _ => Err(format_err!("bad spring position: {}", a)),
             }
         })
-        .count();
-    println!("second answer: {}", answer);
+        .collect::<Result<Vec<(i64, i64)>, Error>>()?;
+    let springs = if springs.is_empty() {
+        vec![(500, 0)]
+    } else {
+        springs
+    };
+
+    let veins = parse_veins(BufReader::new(file))?;
+    let mut reservoir = Reservoir::new(&veins, &springs)?;
+    reservoir.fill();
+    if map {
+        print!("{}", reservoir);
+    }
+    if let Some(path) = ppm {
+        let mut out = BufWriter::new(File::create(path)?);
+        reservoir.write_ppm(&mut out)?;
+    }
+
+    let water = reservoir.water();
+    println!("first answer: {}", water.total());
+    println!("second answer: {}", water.settled);
 
     Ok(())
 }
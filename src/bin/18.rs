@@ -1,79 +1,215 @@
-use std::collections::HashMap;
 use std::env;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 
-use failure::Error;
+use advent2018::cycle::state_after;
+use failure::{bail, ensure, Error};
+use rayon::prelude::*;
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
-enum Acre {
-    Open,
-    Trees,
-    Lumberyard,
+const MINUTES: u64 = 1_000_000_000;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Edges {
+    // Everything past the edge of the map is open ground.
+    Fixed,
+    // The map wraps around, top to bottom and left to right.
+    Toroidal,
 }
 
-fn adjacent(acre: Acre, (x, y): (usize, usize), landscape: &Vec<Vec<Acre>>) -> usize {
-    let neighbors = [
-        (x - 1, y - 1),
-        (x, y - 1),
-        (x + 1, y - 1),
-        (x - 1, y),
-        (x + 1, y),
-        (x - 1, y + 1),
-        (x, y + 1),
-        (x + 1, y + 1),
-    ];
-    neighbors
-        .iter()
-        .filter(|&&(x, y)| landscape.get(y).and_then(|r| r.get(x)) == Some(&acre))
-        .count()
+// Bit-sliced counters, one for each bit position of a word, saturating at four.
+#[derive(Debug, Default, Copy, Clone)]
+struct Counts {
+    ones: u64,
+    twos: u64,
+    fours: u64,
 }
 
-fn tick(landscape: &Vec<Vec<Acre>>) -> Vec<Vec<Acre>> {
-    let mut next = landscape.clone();
-    for (y, row) in landscape.iter().enumerate() {
-        for (x, acre) in row.iter().enumerate() {
-            next[y][x] = match acre {
-                Acre::Open => {
-                    if adjacent(Acre::Trees, (x, y), landscape) >= 3 {
-                        Acre::Trees
-                    } else {
-                        Acre::Open
-                    }
-                }
-                Acre::Trees => {
-                    if adjacent(Acre::Lumberyard, (x, y), landscape) >= 3 {
-                        Acre::Lumberyard
-                    } else {
-                        Acre::Trees
-                    }
-                }
-                Acre::Lumberyard => {
-                    if adjacent(Acre::Lumberyard, (x, y), landscape) >= 1
-                        && adjacent(Acre::Trees, (x, y), landscape) >= 1
-                    {
-                        Acre::Lumberyard
-                    } else {
-                        Acre::Open
-                    }
+impl Counts {
+    fn add(&mut self, bits: u64) {
+        let carry = self.ones & bits;
+        self.ones ^= bits;
+        self.fours |= self.twos & carry;
+        self.twos ^= carry;
+    }
+
+    fn at_least_one(&self) -> u64 {
+        self.ones | self.twos | self.fours
+    }
+
+    fn at_least_three(&self) -> u64 {
+        self.fours | (self.twos & self.ones)
+    }
+}
+
+// The lumber collection area as two bit-planes, one bit per acre in row-major order with each
+// row padded out to a whole number of words. Acres in neither plane are open.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Landscape {
+    width: usize,
+    height: usize,
+    stride: usize,
+    edges: Edges,
+    trees: Vec<u64>,
+    lumberyards: Vec<u64>,
+}
+
+impl Landscape {
+    fn parse<R: BufRead>(input: R, edges: Edges) -> Result<Landscape, Error> {
+        let lines = input
+            .lines()
+            .collect::<Result<Vec<String>, _>>()?
+            .into_iter()
+            .filter(|l| !l.trim().is_empty())
+            .collect::<Vec<String>>();
+        ensure!(!lines.is_empty(), "empty landscape");
+
+        let width = lines[0].trim().len();
+        let stride = (width + 63) >> 6;
+        let mut landscape = Landscape {
+            width,
+            height: lines.len(),
+            stride,
+            edges,
+            trees: vec![0; stride * lines.len()],
+            lumberyards: vec![0; stride * lines.len()],
+        };
+
+        for (y, line) in lines.iter().enumerate() {
+            let line = line.trim();
+            ensure!(line.len() == width, "ragged landscape at row {}", y);
+            for (x, c) in line.chars().enumerate() {
+                let (word, bit) = (y * stride + x / 64, 1 << (x % 64));
+                match c {
+                    '.' => (),
+                    '|' => landscape.trees[word] |= bit,
+                    '#' => landscape.lumberyards[word] |= bit,
+                    _ => bail!("bad input: {}", c),
                 }
             }
         }
+        Ok(landscape)
+    }
+
+    fn row<'a>(&self, plane: &'a [u64], y: usize) -> &'a [u64] {
+        &plane[y * self.stride..(y + 1) * self.stride]
+    }
+
+    // Word i of a row moved one acre along, so that each bit holds its western (or eastern)
+    // neighbour; the carries come from the next word over, or the far end of the row if the
+    // map wraps.
+    fn west(&self, row: &[u64], i: usize) -> u64 {
+        let carry = if i > 0 {
+            row[i - 1] >> 63
+        } else if self.edges == Edges::Toroidal {
+            let x = self.width - 1;
+            (row[x / 64] >> (x % 64)) & 1
+        } else {
+            0
+        };
+        (row[i] << 1) | carry
+    }
+
+    fn east(&self, row: &[u64], i: usize) -> u64 {
+        let mut word = row[i] >> 1;
+        if i + 1 < self.stride {
+            word |= row[i + 1] << 63;
+        } else if self.edges == Edges::Toroidal {
+            word |= (row[0] & 1) << ((self.width - 1) % 64);
+        }
+        word
+    }
+
+    // Bits of word i that are real acres rather than padding.
+    fn mask(&self, i: usize) -> u64 {
+        let used = self.width - 64 * i;
+        if used >= 64 {
+            !0
+        } else {
+            (1 << used) - 1
+        }
+    }
+
+    // Adds the acres of a row around each bit of word i to the counts; the middle ones are left
+    // out on the acre's own row.
+    fn count(&self, row: &[u64], i: usize, include_middle: bool, counts: &mut Counts) {
+        counts.add(self.west(row, i));
+        counts.add(self.east(row, i));
+        if include_middle {
+            counts.add(row[i]);
+        }
+    }
+
+    fn tick_row(&self, y: usize, trees: &mut [u64], lumberyards: &mut [u64]) {
+        let wrap = self.edges == Edges::Toroidal;
+        let above = if y > 0 {
+            Some(y - 1)
+        } else if wrap {
+            Some(self.height - 1)
+        } else {
+            None
+        };
+        let below = if y + 1 < self.height {
+            Some(y + 1)
+        } else if wrap {
+            Some(0)
+        } else {
+            None
+        };
+        let neighbours = [(above, true), (Some(y), false), (below, true)];
+
+        for i in 0..self.stride {
+            let mut adjacent_trees = Counts::default();
+            let mut adjacent_lumberyards = Counts::default();
+            for &(row, include_middle) in neighbours.iter() {
+                let row = match row {
+                    Some(row) => row,
+                    None => continue,
+                };
+                self.count(
+                    self.row(&self.trees, row),
+                    i,
+                    include_middle,
+                    &mut adjacent_trees,
+                );
+                self.count(
+                    self.row(&self.lumberyards, row),
+                    i,
+                    include_middle,
+                    &mut adjacent_lumberyards,
+                );
+            }
+
+            let here = y * self.stride + i;
+            let (t, l) = (self.trees[here], self.lumberyards[here]);
+            let open = !(t | l);
+            trees[i] = ((open & adjacent_trees.at_least_three())
+                | (t & !adjacent_lumberyards.at_least_three()))
+                & self.mask(i);
+            lumberyards[i] = ((t & adjacent_lumberyards.at_least_three())
+                | (l & adjacent_lumberyards.at_least_one() & adjacent_trees.at_least_one()))
+                & self.mask(i);
+        }
+    }
+
+    fn tick(&self) -> Landscape {
+        let mut next = Landscape {
+            trees: vec![0; self.trees.len()],
+            lumberyards: vec![0; self.lumberyards.len()],
+            ..*self
+        };
+        next.trees
+            .par_chunks_mut(self.stride)
+            .zip(next.lumberyards.par_chunks_mut(self.stride))
+            .enumerate()
+            .for_each(|(y, (trees, lumberyards))| self.tick_row(y, trees, lumberyards));
+        next
     }
-    next
 }
 
-fn print_score(landscape: &Vec<Vec<Acre>>) {
-    let wooded = landscape
-        .iter()
-        .flat_map(|r| r.iter())
-        .filter(|&&a| a == Acre::Trees)
-        .count();
-    let lumberyards = landscape
-        .iter()
-        .flat_map(|r| r.iter())
-        .filter(|&&a| a == Acre::Lumberyard)
-        .count();
+fn print_score(landscape: &Landscape) {
+    let count = |plane: &[u64]| -> u64 { plane.iter().map(|w| u64::from(w.count_ones())).sum() };
+    let wooded = count(&landscape.trees);
+    let lumberyards = count(&landscape.lumberyards);
     println!("{} * {} = {}", wooded, lumberyards, wooded * lumberyards);
 }
 
@@ -81,47 +217,31 @@ fn main() -> Result<(), Error> {
     let args: Vec<String> = env::args().collect();
     let file = File::open(&args[1])?;
 
-    let mut landscape: Vec<Vec<Acre>> = Vec::new();
-    for line in BufReader::new(file).lines().map(|l| l.unwrap()) {
-        landscape.push(
-            line.chars()
-                .map(|c| match c {
-                    '.' => Acre::Open,
-                    '|' => Acre::Trees,
-                    '#' => Acre::Lumberyard,
-                    _ => panic!("bad input: {}", c),
-                })
-                .collect(),
-        );
-    }
-
-    let mut cache = HashMap::new();
+    let edges = if args[2..].iter().any(|a| a == "--torus") {
+        Edges::Toroidal
+    } else {
+        Edges::Fixed
+    };
+    let mut landscape = Landscape::parse(BufReader::new(file), edges)?;
 
-    for elapsed in 1..=10 {
-        landscape = tick(&landscape);
-        cache.insert(landscape.clone(), elapsed);
+    for _ in 1..=10 {
+        landscape = landscape.tick();
     }
     print_score(&landscape);
 
-    for elapsed in 11..=1000000000 {
-        landscape = tick(&landscape);
-
-        if let Some(previous) = cache.get(&landscape) {
-            println!("Found repeated pattern after {} minutes.", elapsed);
-            println!("Previously seen after {} minutes.", previous);
-            let remaining_minutes = 1000000000 - elapsed;
-            let period = elapsed - previous;
-            let cycles = remaining_minutes / period;
-            println!("Repetition ({} min): skip {} cycles.", period, cycles);
-            let completion = remaining_minutes % period;
-            println!("{} steps remain to find the end.", completion);
-            for _ in 0..completion {
-                landscape = tick(&landscape);
-            }
-            break;
-        }
-
-        cache.insert(landscape.clone(), elapsed);
+    let remaining = MINUTES - 10;
+    let (landscape, cycle) = state_after(landscape, remaining, Landscape::tick, Landscape::eq);
+    if let Some(cycle) = cycle {
+        println!(
+            "Found repeated pattern after {} minutes.",
+            10 + cycle.start + cycle.period
+        );
+        println!("Previously seen after {} minutes.", 10 + cycle.start);
+        println!(
+            "Repetition ({} min): skip {} cycles.",
+            cycle.period,
+            (remaining - cycle.start) / cycle.period
+        );
     }
     print_score(&landscape);
 
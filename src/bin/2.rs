@@ -15,11 +15,99 @@ fn matches(a: &str, b: &str) -> String {
         .collect()
 }
 
+// Two IDs, by index, that differ at exactly the listed positions.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct NearPair {
+    a: usize,
+    b: usize,
+    positions: Vec<usize>,
+}
+
+// Steps `combination` to the next k-subset of 0..n in lexicographic order.
+fn next_combination(combination: &mut [usize], n: usize) -> bool {
+    let k = combination.len();
+    for i in (0..k).rev() {
+        if combination[i] < n - k + i {
+            combination[i] += 1;
+            for j in i + 1..k {
+                combination[j] = combination[j - 1] + 1;
+            }
+            return true;
+        }
+    }
+    false
+}
+
+// Finds every pair of IDs that differ in exactly k positions. For each choice of k positions,
+// IDs that agree once those positions are deleted land in the same bucket, and a pair that also
+// differs at every one of the chosen positions differs at exactly those; so each pair turns up
+// under exactly one choice.
+fn near_duplicates(ids: &[String], k: usize) -> Vec<NearPair> {
+    let ids: Vec<Vec<char>> = ids.iter().map(|id| id.chars().collect()).collect();
+    let mut by_length: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (i, id) in ids.iter().enumerate() {
+        by_length.entry(id.len()).or_default().push(i);
+    }
+
+    let mut pairs = Vec::new();
+    for (&len, group) in by_length.iter() {
+        if k > len {
+            continue;
+        }
+
+        let mut positions: Vec<usize> = (0..k).collect();
+        loop {
+            let mut buckets: HashMap<Vec<char>, Vec<usize>> = HashMap::new();
+            for &i in group.iter() {
+                let mut deleted = positions.iter().peekable();
+                let key: Vec<char> = ids[i]
+                    .iter()
+                    .enumerate()
+                    .filter(|&(p, _)| {
+                        if deleted.peek() == Some(&&p) {
+                            deleted.next();
+                            false
+                        } else {
+                            true
+                        }
+                    })
+                    .map(|(_, &c)| c)
+                    .collect();
+                buckets.entry(key).or_default().push(i);
+            }
+
+            for bucket in buckets.values() {
+                for (n, &a) in bucket.iter().enumerate() {
+                    for &b in bucket[n + 1..].iter() {
+                        if positions.iter().all(|&p| ids[a][p] != ids[b][p]) {
+                            pairs.push(NearPair {
+                                a,
+                                b,
+                                positions: positions.clone(),
+                            });
+                        }
+                    }
+                }
+            }
+
+            if !next_combination(&mut positions, len) {
+                break;
+            }
+        }
+    }
+
+    pairs.sort();
+    pairs
+}
+
 fn main() -> Result<(), Error> {
     let args: Vec<String> = env::args().collect();
     let file = File::open(&args[1])?;
 
-    let mut box_ids: Vec<String> = BufReader::new(file)
+    // Optional argument: how many positions near-duplicate IDs differ in.
+    let k = args.get(2).map_or(Ok(1), |k| k.parse())?;
+
+    let box_ids: Vec<String> = BufReader::new(file)
         .lines()
         .map(|l| l.expect("file read failed"))
         .collect();
@@ -40,14 +128,15 @@ fn main() -> Result<(), Error> {
     }
     println!("checksum {}", twos * threes);
 
-    box_ids.sort();
-    for ids in box_ids.windows(2) {
-        let id_len = ids[0].len();
-        let matches = matches(&ids[0], &ids[1]);
-        let distance = id_len - matches.len();
-        if distance == 1 {
-            println!("{}", matches);
-        }
+    for pair in near_duplicates(&box_ids, k) {
+        let (a, b) = (&box_ids[pair.a], &box_ids[pair.b]);
+        println!(
+            "{} and {} differ at {:?}: {}",
+            a,
+            b,
+            pair.positions,
+            matches(a, b)
+        );
     }
 
     Ok(())
@@ -1,11 +1,12 @@
 #[macro_use]
 extern crate lazy_static;
 
+use std::collections::BTreeMap;
 use std::env;
 use std::fmt;
 use std::fs::File;
 use std::io::prelude::*;
-use std::ops::{Index, IndexMut};
+use std::ops::Index;
 use std::str::FromStr;
 
 use failure::{format_err, Error};
@@ -23,145 +24,164 @@ enum Army {
     Infection,
 }
 
-#[derive(Debug, Copy, Clone)]
-enum AttackType {
-    Fire,
-    Cold,
-    Slashing,
-    Bludgeoning,
-    Radiation,
-}
-
-impl FromStr for AttackType {
-    type Err = Error;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(match s {
-            "fire" => AttackType::Fire,
-            "cold" => AttackType::Cold,
-            "slashing" => AttackType::Slashing,
-            "bludgeoning" => AttackType::Bludgeoning,
-            "radiation" => AttackType::Radiation,
-            _ => return Err(format_err!("bad attack type: {}", s)),
+impl fmt::Display for Army {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Army::ImmuneSystem => "Immune System",
+            Army::Infection => "Infection",
         })
     }
 }
 
-#[derive(Debug, Copy, Clone)]
-struct Defenses {
-    fire: usize,
-    cold: usize,
-    slashing: usize,
-    bludgeoning: usize,
-    radiation: usize,
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+struct AttackType(usize);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct DefensesId(usize);
+
+// Groups have to stay `Copy` to live in a slotmap, so attack type names and each group's
+// defenses are interned here at parse time and referred to by index.
+#[derive(Debug, Clone, Default)]
+struct DamageRules {
+    names: Vec<String>,
+    defenses: Vec<Defenses>,
 }
 
-impl Default for Defenses {
-    fn default() -> Defenses {
-        Defenses {
-            fire: 1,
-            cold: 1,
-            slashing: 1,
-            bludgeoning: 1,
-            radiation: 1,
+impl DamageRules {
+    fn intern(&mut self, name: &str) -> Result<AttackType, Error> {
+        let name = name.trim();
+        if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            return Err(format_err!("bad attack type: {:?}", name));
+        }
+
+        if let Some(i) = self.names.iter().position(|n| n == name) {
+            return Ok(AttackType(i));
         }
+        self.names.push(name.to_string());
+        Ok(AttackType(self.names.len() - 1))
+    }
+
+    fn name(&self, attack_type: AttackType) -> &str {
+        &self.names[attack_type.0]
+    }
+
+    fn add_defenses(&mut self, defenses: Defenses) -> DefensesId {
+        self.defenses.push(defenses);
+        DefensesId(self.defenses.len() - 1)
+    }
+
+    fn defenses(&self, id: DefensesId) -> &Defenses {
+        &self.defenses[id.0]
     }
 }
 
+// One clause of a group's defenses as it was written; "weak to" and "immune to" are just 2x and
+// 0x damage under other names.
+#[derive(Debug, Clone)]
+struct Clause {
+    modifier: usize,
+    // Whether the modifier was spelled out as "Nx damage from".
+    explicit: bool,
+    types: Vec<AttackType>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Defenses {
+    // Damage multipliers for each attack type; anything missing takes normal damage.
+    modifiers: BTreeMap<AttackType, usize>,
+    // The clauses behind the modifiers, in order, so they can be written back out as they were.
+    clauses: Vec<Clause>,
+}
+
 impl Index<AttackType> for Defenses {
     type Output = usize;
 
     fn index(&self, attack_type: AttackType) -> &usize {
-        match attack_type {
-            AttackType::Fire => &self.fire,
-            AttackType::Cold => &self.cold,
-            AttackType::Slashing => &self.slashing,
-            AttackType::Bludgeoning => &self.bludgeoning,
-            AttackType::Radiation => &self.radiation,
-        }
-    }
-}
-
-impl IndexMut<AttackType> for Defenses {
-    fn index_mut(&mut self, attack_type: AttackType) -> &mut usize {
-        match attack_type {
-            AttackType::Fire => &mut self.fire,
-            AttackType::Cold => &mut self.cold,
-            AttackType::Slashing => &mut self.slashing,
-            AttackType::Bludgeoning => &mut self.bludgeoning,
-            AttackType::Radiation => &mut self.radiation,
-        }
+        self.modifiers.get(&attack_type).unwrap_or(&1)
     }
 }
 
 impl Defenses {
-    fn with_weaknesses(mut self, types: &Vec<AttackType>) -> Defenses {
-        for &weakness in types.iter() {
-            self[weakness] = 2;
+    fn with_clause(mut self, clause: Clause) -> Defenses {
+        for &attack_type in clause.types.iter() {
+            self.modifiers.insert(attack_type, clause.modifier);
         }
+        self.clauses.push(clause);
         self
     }
 
-    fn with_immunities(mut self, types: &Vec<AttackType>) -> Defenses {
-        for &immunity in types.iter() {
-            self[immunity] = 0;
-        }
-        self
-    }
-}
-
-impl FromStr for Defenses {
-    type Err = Error;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
+    fn parse(s: &str, rules: &mut DamageRules) -> Result<Defenses, Error> {
         lazy_static! {
-            static ref RE: Regex = Regex::new(
-                r"(?:(?:(?:weak to (?P<weak>[^;]+))|(?:immune to (?P<immune>[^;]+)))(?:; )?){0,2}"
-            )
-            .unwrap();
+            static ref RE: Regex =
+                Regex::new(r"^(?:weak to (?P<weak>.+)|immune to (?P<immune>.+)|(?P<modifier>\d+)x damage from (?P<types>.+))$")
+                    .unwrap();
         }
 
         let mut defenses: Defenses = Default::default();
-
-        let captures = RE
-            .captures(&s)
-            .ok_or_else(|| format_err!("defenses match failed: {}", s))?;
-
-        if let Some(weaknesses) = captures.name("weak") {
-            let w = weaknesses
-                .as_str()
-                .split(", ")
-                .map(|s| s.parse())
+        for clause in s.split(';').map(|c| c.trim()) {
+            let captures = RE
+                .captures(clause)
+                .ok_or_else(|| format_err!("defenses match failed: {}", s))?;
+
+            let (modifier, explicit, list) = if let Some(weaknesses) = captures.name("weak") {
+                (2, false, weaknesses.as_str())
+            } else if let Some(immunities) = captures.name("immune") {
+                (0, false, immunities.as_str())
+            } else {
+                (captures["modifier"].parse()?, true, &captures["types"])
+            };
+
+            let types = list
+                .split(',')
+                .map(|t| rules.intern(t))
                 .collect::<Result<Vec<AttackType>, _>>()?;
-            defenses = defenses.with_weaknesses(&w);
-        }
-
-        if let Some(immunities) = captures.name("immune") {
-            let i = immunities
-                .as_str()
-                .split(", ")
-                .map(|s| s.parse())
-                .collect::<Result<Vec<AttackType>, _>>()?;
-            defenses = defenses.with_immunities(&i);
+            defenses = defenses.with_clause(Clause {
+                modifier,
+                explicit,
+                types,
+            });
         }
 
         Ok(defenses)
     }
+
+    // The clauses as they were parsed, in the same order; only the spacing is tidied up.
+    fn serialize(&self, rules: &DamageRules) -> String {
+        let clauses: Vec<String> = self
+            .clauses
+            .iter()
+            .map(|clause| {
+                let types: Vec<&str> = clause.types.iter().map(|&t| rules.name(t)).collect();
+                match (clause.modifier, clause.explicit) {
+                    (2, false) => format!("weak to {}", types.join(", ")),
+                    (0, false) => format!("immune to {}", types.join(", ")),
+                    (modifier, _) => format!("{}x damage from {}", modifier, types.join(", ")),
+                }
+            })
+            .collect();
+        clauses.join("; ")
+    }
 }
 
 #[derive(Debug, Copy, Clone)]
 struct Group {
     army: ArmyKey,
+    index: usize,
     units: usize,
     hit_points: usize,
     attack_damage: usize,
     attack_type: AttackType,
     initiative: usize,
-    defenses: Defenses,
+    defenses: DefensesId,
 }
 
 impl Group {
-    fn from_str(s: &str, army: ArmyKey) -> Result<Group, Error> {
+    fn parse(
+        s: &str,
+        army: ArmyKey,
+        index: usize,
+        rules: &mut DamageRules,
+    ) -> Result<Group, Error> {
         lazy_static! {
             static ref RE: Regex = Regex::new(r"(\d+) units each with (\d+) hit points (?:\(([^\)]+)\) )?with an attack that does (\d+) (\w+) damage at initiative (\d+)").unwrap();
         }
@@ -172,28 +192,53 @@ impl Group {
 
         Ok(Group {
             army,
+            index,
             units: captures[1].parse()?,
             hit_points: captures[2].parse()?,
             attack_damage: captures[4].parse()?,
-            attack_type: captures[5].parse()?,
+            attack_type: rules.intern(&captures[5])?,
             initiative: captures[6].parse()?,
-            defenses: captures
-                .get(3)
-                .map(|s| s.as_str().parse())
-                .unwrap_or_else(|| Ok(Default::default()))?,
+            defenses: match captures.get(3) {
+                Some(defenses) => {
+                    let defenses = Defenses::parse(defenses.as_str(), rules)?;
+                    rules.add_defenses(defenses)
+                }
+                None => rules.add_defenses(Default::default()),
+            },
         })
     }
 
+    fn serialize(&self, rules: &DamageRules) -> String {
+        let defenses = rules.defenses(self.defenses).serialize(rules);
+        format!(
+            "{} units each with {} hit points {}with an attack that does {} {} damage at initiative {}",
+            self.units,
+            self.hit_points,
+            if defenses.is_empty() {
+                String::new()
+            } else {
+                format!("({}) ", defenses)
+            },
+            self.attack_damage,
+            rules.name(self.attack_type),
+            self.initiative
+        )
+    }
+
     fn effective_power(&self) -> usize {
         self.units * self.attack_damage
     }
 
-    fn damage_against(&self, target: &Group) -> usize {
-        let modifier = target.defenses[self.attack_type];
+    fn damage_against(&self, target: &Group, rules: &DamageRules) -> usize {
+        let modifier = rules.defenses(target.defenses)[self.attack_type];
         self.effective_power() * modifier
     }
 
-    fn pick_target(&self, groups: &SlotMap<GroupKey, Group>) -> Option<GroupKey> {
+    fn pick_target(
+        &self,
+        groups: &SlotMap<GroupKey, Group>,
+        rules: &DamageRules,
+    ) -> Option<GroupKey> {
         let mut candidates: Vec<GroupKey> = groups
             .keys()
             .filter(|&k| groups[k].army != self.army)
@@ -202,7 +247,7 @@ impl Group {
         if let Some(most_damage) = candidates
             .iter()
             .map(|&k| {
-                let damage = self.damage_against(&groups[k]);
+                let damage = self.damage_against(&groups[k], rules);
                 damage
             })
             .max()
@@ -211,7 +256,7 @@ impl Group {
             if most_damage == 0 {
                 return None;
             }
-            candidates.retain(|&k| self.damage_against(&groups[k]) == most_damage);
+            candidates.retain(|&k| self.damage_against(&groups[k], rules) == most_damage);
         }
 
         if let Some(largest_power) = candidates
@@ -226,8 +271,7 @@ impl Group {
         candidates.first().map(|&k| k)
     }
 
-    fn take_damage(&mut self, attacker: &Group) {
-        let damage = attacker.damage_against(self);
+    fn take_damage(&mut self, damage: usize) {
         let units_lost = std::cmp::min(self.units, damage / self.hit_points);
         self.units -= units_lost;
     }
@@ -237,26 +281,27 @@ impl Group {
 struct Game {
     armies: SlotMap<ArmyKey, Army>,
     groups: SlotMap<GroupKey, Group>,
+    rules: DamageRules,
 }
 
 impl fmt::Display for Game {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        writeln!(f, "Immune System:")?;
-        for (k, group) in self
-            .groups
-            .iter()
-            .filter(|(_, g)| self.armies[g.army] == Army::ImmuneSystem)
-        {
-            writeln!(f, "Group {:?} contains {} units", k, group.units)?;
-        }
+        for &army in [Army::ImmuneSystem, Army::Infection].iter() {
+            writeln!(f, "{}:", army)?;
+
+            let mut groups: Vec<&Group> = self
+                .groups
+                .values()
+                .filter(|g| self.armies[g.army] == army)
+                .collect();
+            if groups.is_empty() {
+                writeln!(f, "No groups remain.")?;
+            }
 
-        writeln!(f, "Infection:")?;
-        for (k, group) in self
-            .groups
-            .iter()
-            .filter(|(_, g)| self.armies[g.army] == Army::Infection)
-        {
-            writeln!(f, "Group {:?} contains {} units", k, group.units)?;
+            groups.sort_unstable_by_key(|g| g.index);
+            for group in groups {
+                writeln!(f, "Group {} contains {} units", group.index, group.units)?;
+            }
         }
 
         Ok(())
@@ -272,15 +317,16 @@ impl FromStr for Game {
         let infection = armies.insert(Army::Infection);
 
         let mut groups = SlotMap::with_key();
+        let mut rules = DamageRules::default();
 
-        let mut input = s.split("\n\n");
+        let mut input = s.trim().split("\n\n");
         let mut lines = input
             .next()
             .ok_or_else(|| format_err!("missing immune team"))?
             .split("\n");
         lines.next(); // skip name
-        for line in lines {
-            groups.insert(Group::from_str(line, immune_system)?);
+        for (i, line) in lines.enumerate() {
+            groups.insert(Group::parse(line, immune_system, i + 1, &mut rules)?);
         }
 
         let mut lines = input
@@ -288,11 +334,15 @@ impl FromStr for Game {
             .ok_or_else(|| format_err!("missing infection team"))?
             .split("\n");
         lines.next(); // skip name
-        for line in lines {
-            groups.insert(Group::from_str(line, infection)?);
+        for (i, line) in lines.enumerate() {
+            groups.insert(Group::parse(line, infection, i + 1, &mut rules)?);
         }
 
-        Ok(Game { armies, groups })
+        Ok(Game {
+            armies,
+            groups,
+            rules,
+        })
     }
 }
 
@@ -301,8 +351,97 @@ struct Attack {
     defender: GroupKey,
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Event {
+    TargetSelection {
+        army: Army,
+        attacker: usize,
+        defender: usize,
+        damage: usize,
+    },
+    DamageDealt {
+        army: Army,
+        attacker: usize,
+        defender: usize,
+        damage: usize,
+    },
+    UnitsKilled {
+        army: Army,
+        attacker: usize,
+        defender: usize,
+        units: usize,
+    },
+    GroupEliminated {
+        army: Army,
+        group: usize,
+    },
+}
+
+fn format_events(events: &[Event]) -> String {
+    // The puzzle lists every target considered by army (infection first) and group number,
+    // not in the order the groups actually made their picks.
+    let mut selections: Vec<(Army, usize, usize, usize)> = events
+        .iter()
+        .filter_map(|event| match *event {
+            Event::TargetSelection {
+                army,
+                attacker,
+                defender,
+                damage,
+            } => Some((army, attacker, defender, damage)),
+            _ => None,
+        })
+        .collect();
+    selections.sort_by_key(|&(army, attacker, defender, _)| {
+        (army == Army::ImmuneSystem, attacker, defender)
+    });
+
+    let mut out = String::new();
+    for (army, attacker, defender, damage) in selections {
+        out += &format!(
+            "{} group {} would deal defending group {} {} damage\n",
+            army, attacker, defender, damage
+        );
+    }
+    out.push('\n');
+
+    for event in events {
+        if let Event::UnitsKilled {
+            army,
+            attacker,
+            defender,
+            units,
+        } = *event
+        {
+            out += &format!(
+                "{} group {} attacks defending group {}, killing {} units\n",
+                army, attacker, defender, units
+            );
+        }
+    }
+
+    out
+}
+
 impl Game {
-    fn select_targets(&self) -> Vec<Attack> {
+    fn serialize(&self) -> String {
+        let mut sections = Vec::new();
+        for &army in [Army::ImmuneSystem, Army::Infection].iter() {
+            let mut groups: Vec<&Group> = self
+                .groups
+                .values()
+                .filter(|g| self.armies[g.army] == army)
+                .collect();
+            groups.sort_unstable_by_key(|g| g.index);
+
+            let mut lines = vec![format!("{}:", army)];
+            lines.extend(groups.iter().map(|g| g.serialize(&self.rules)));
+            sections.push(lines.join("\n"));
+        }
+        sections.join("\n\n")
+    }
+
+    fn select_targets(&self, events: &mut Vec<Event>) -> Vec<Attack> {
         let mut attackers: Vec<GroupKey> = self.groups.keys().collect();
         attackers.sort_unstable_by_key(|&k| {
             (
@@ -314,8 +453,17 @@ impl Game {
         let mut attacks = Vec::with_capacity(self.groups.len());
         let mut targets = self.groups.clone();
         for attacker in attackers {
-            let group = self.groups[attacker];
-            if let Some(defender) = group.pick_target(&targets) {
+            let group = &self.groups[attacker];
+            for target in targets.values().filter(|t| t.army != group.army) {
+                events.push(Event::TargetSelection {
+                    army: self.armies[group.army],
+                    attacker: group.index,
+                    defender: target.index,
+                    damage: group.damage_against(target, &self.rules),
+                });
+            }
+
+            if let Some(defender) = group.pick_target(&targets, &self.rules) {
                 attacks.push(Attack { attacker, defender });
                 targets.remove(defender);
             }
@@ -324,60 +472,177 @@ impl Game {
         attacks
     }
 
-    fn resolve_attacks(&mut self, mut attacks: Vec<Attack>) -> usize {
+    fn resolve_attacks(&mut self, mut attacks: Vec<Attack>, events: &mut Vec<Event>) -> usize {
         let mut killed = 0;
 
         attacks.sort_unstable_by_key(|a| self.groups[a.attacker].initiative as i64 * -1);
         for attack in attacks {
-            let attacker = self.groups[attack.attacker];
+            let attacker = &self.groups[attack.attacker];
 
             // Require the group to still be alive to allow the attack.
             if attacker.units == 0 {
                 continue;
             }
 
+            let army = self.armies[attacker.army];
+            let index = attacker.index;
+            let damage = attacker.damage_against(&self.groups[attack.defender], &self.rules);
+
             let defender = self.groups.get_mut(attack.defender).unwrap();
             let before = defender.units;
-            defender.take_damage(&attacker);
+            defender.take_damage(damage);
             let after = defender.units;
             killed += before - after;
-            println!(
-                "{:?} attacks {:?}, killing {} units",
-                attack.attacker,
-                attack.defender,
-                before - after
-            );
+
+            events.push(Event::DamageDealt {
+                army,
+                attacker: index,
+                defender: defender.index,
+                damage,
+            });
+            events.push(Event::UnitsKilled {
+                army,
+                attacker: index,
+                defender: defender.index,
+                units: before - after,
+            });
+            if after == 0 {
+                events.push(Event::GroupEliminated {
+                    army: self.armies[defender.army],
+                    group: defender.index,
+                });
+            }
         }
 
         killed
     }
 
-    fn fight(&mut self) -> bool {
-        let attacks = self.select_targets();
-        let killed = self.resolve_attacks(attacks);
+    fn fight(&mut self, events: &mut Vec<Event>) -> bool {
+        let attacks = self.select_targets(events);
+        let killed = self.resolve_attacks(attacks, events);
         self.groups.retain(|_, group| group.units > 0);
         killed != 0
     }
 
-    fn is_resolved(&self) -> bool {
-        for army in self.armies.keys() {
-            if self.groups.values().filter(|g| g.army == army).count() == 0 {
-                return true;
-            }
+    fn winner(&self) -> Option<Army> {
+        let mut survivors = self.groups.values().map(|g| g.army);
+        let first = survivors.next()?;
+        if survivors.all(|army| army == first) {
+            Some(self.armies[first])
+        } else {
+            None
         }
-        false
     }
 
     fn answer(&self) -> usize {
         self.groups.values().map(|g| g.units).sum()
     }
 
-    fn reindeer_lives(&self) -> bool {
-        let key = self.groups.keys().next().unwrap();
-        self.armies[self.groups[key].army] == Army::ImmuneSystem
+    fn simulate<F>(&mut self, max_rounds: usize, mut observe: F) -> Outcome
+    where
+        F: FnMut(&Game, &[Event]),
+    {
+        let mut rounds = 0;
+        let mut events = Vec::new();
+        let resolution = loop {
+            if let Some(army) = self.winner() {
+                break Resolution::Victory(army);
+            }
+            if rounds == max_rounds {
+                break Resolution::Timeout;
+            }
+            rounds += 1;
+            events.clear();
+            let progress = self.fight(&mut events);
+            observe(self, &events);
+            if !progress {
+                break Resolution::Stalemate;
+            }
+        };
+
+        Outcome {
+            resolution,
+            rounds,
+            units: self.answer(),
+        }
+    }
+
+    fn boosted(&self, boost: usize) -> Game {
+        let mut boosted = self.clone();
+        for group in boosted.groups.values_mut() {
+            if boosted.armies[group.army] == Army::ImmuneSystem {
+                group.attack_damage += boost;
+            }
+        }
+        boosted
+    }
+
+    fn find_min_boost(&self, max_boost: usize, max_rounds: usize) -> Option<(usize, Outcome)> {
+        let infection_wins = |boost| {
+            self.boosted(boost)
+                .simulate(max_rounds, |_, _| ())
+                .resolution
+                == Resolution::Victory(Army::Infection)
+        };
+
+        if infection_wins(max_boost) {
+            return None;
+        }
+
+        // Outright losses only get rarer as the boost grows, but stalemates can show up in
+        // bands on either side of a win. Binary search for the first boost the infection
+        // can't win with, then walk forward past any stalemates to the first real victory.
+        let (mut lo, mut hi) = (0, max_boost);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            if infection_wins(mid) {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+
+        (lo..=max_boost)
+            .map(|boost| {
+                let outcome = self.boosted(boost).simulate(max_rounds, |_, _| ());
+                (boost, outcome)
+            })
+            .find(|(_, outcome)| outcome.resolution == Resolution::Victory(Army::ImmuneSystem))
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Resolution {
+    Victory(Army),
+    Stalemate,
+    Timeout,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Outcome {
+    resolution: Resolution,
+    rounds: usize,
+    units: usize,
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.resolution {
+            Resolution::Victory(army) => write!(f, "{:?} wins", army)?,
+            Resolution::Stalemate => write!(f, "stalemate")?,
+            Resolution::Timeout => write!(f, "timeout")?,
+        }
+        write!(
+            f,
+            " after {} rounds with {} units left",
+            self.rounds, self.units
+        )
     }
 }
 
+const MAX_ROUNDS: usize = 100_000;
+const MAX_BOOST: usize = 1 << 16;
+
 fn main() -> Result<(), Error> {
     let args: Vec<String> = env::args().collect();
     let mut file = File::open(&args[1])?;
@@ -387,28 +652,178 @@ fn main() -> Result<(), Error> {
 
     let game: Game = input.parse()?;
 
-    'outer: for boost in 1.. {
-        let mut boosted = game.clone();
-        for group in boosted.groups.values_mut() {
-            if boosted.armies[group.army] == Army::ImmuneSystem {
-                group.attack_damage += boost;
-            }
-        }
+    // --serialize writes the armies back out just as they were given, apart from spacing.
+    if args.iter().skip(2).any(|arg| arg == "--serialize") {
+        println!("{}", game.serialize());
+        return Ok(());
+    }
 
-        while !boosted.is_resolved() {
-            println!("\n\n{}", boosted);
-            if !boosted.fight() {
-                continue 'outer;
-            }
+    let log = args.iter().skip(2).any(|arg| arg == "--log");
+    let mut battle = game.clone();
+    if log {
+        println!("{}", battle);
+    }
+    let outcome = battle.simulate(MAX_ROUNDS, |state, events| {
+        if log {
+            println!("{}\n{}", format_events(events), state);
+        }
+    });
+    println!("unboosted: {}", outcome);
+    println!("answer: {}", outcome.units);
+
+    match game.find_min_boost(MAX_BOOST, MAX_ROUNDS) {
+        Some((boost, outcome)) => {
+            println!("boost {}: {}", boost, outcome);
+            println!("answer: {}", outcome.units);
         }
+        None => println!("no boost up to {} saves the reindeer", MAX_BOOST),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A group as it reads in the description, with attack types by name since interning can
+    // number them differently from one parse to the next.
+    type Description = (
+        String,
+        usize,
+        usize,
+        usize,
+        usize,
+        String,
+        usize,
+        BTreeMap<String, usize>,
+    );
+
+    fn describe(game: &Game) -> Vec<Description> {
+        let mut groups: Vec<Description> = game
+            .groups
+            .values()
+            .map(|g| {
+                let defenses = game
+                    .rules
+                    .defenses(g.defenses)
+                    .modifiers
+                    .iter()
+                    .filter(|(_, &m)| m != 1)
+                    .map(|(&t, &m)| (game.rules.name(t).to_string(), m))
+                    .collect();
+                (
+                    game.armies[g.army].to_string(),
+                    g.index,
+                    g.units,
+                    g.hit_points,
+                    g.attack_damage,
+                    game.rules.name(g.attack_type).to_string(),
+                    g.initiative,
+                    defenses,
+                )
+            })
+            .collect();
+        groups.sort();
+        groups
+    }
 
-        println!("\n\n{}", boosted);
+    fn assert_round_trip(input: &str) {
+        let game: Game = input.parse().unwrap();
+        let serialized = game.serialize();
+        let reparsed: Game = serialized.parse().unwrap();
+        assert_eq!(describe(&reparsed), describe(&game), "{}", serialized);
+        assert_eq!(serialized, input.trim());
+    }
 
-        if boosted.reindeer_lives() {
-            println!("answer: {}", boosted.answer());
-            break;
+    #[test]
+    fn data_files_round_trip() {
+        for path in ["dat/24/example", "dat/24/input"].iter() {
+            let mut input = String::new();
+            File::open(path)
+                .unwrap()
+                .read_to_string(&mut input)
+                .unwrap();
+            assert_round_trip(&input);
         }
     }
 
-    Ok(())
+    // The first round of the puzzle's example, as the puzzle tells it.
+    const EXAMPLE_ROUND: &str = "\
+Immune System:
+Group 1 contains 17 units
+Group 2 contains 989 units
+Infection:
+Group 1 contains 801 units
+Group 2 contains 4485 units
+
+Infection group 1 would deal defending group 1 185832 damage
+Infection group 1 would deal defending group 2 185832 damage
+Infection group 2 would deal defending group 2 107640 damage
+Immune System group 1 would deal defending group 1 76619 damage
+Immune System group 1 would deal defending group 2 153238 damage
+Immune System group 2 would deal defending group 1 24725 damage
+
+Infection group 2 attacks defending group 2, killing 84 units
+Immune System group 2 attacks defending group 1, killing 4 units
+Immune System group 1 attacks defending group 2, killing 51 units
+Infection group 1 attacks defending group 1, killing 17 units
+
+Immune System:
+Group 2 contains 905 units
+Infection:
+Group 1 contains 797 units
+Group 2 contains 4434 units
+";
+
+    #[test]
+    fn example_round_matches_puzzle() {
+        let mut input = String::new();
+        File::open("dat/24/example")
+            .unwrap()
+            .read_to_string(&mut input)
+            .unwrap();
+        let mut game: Game = input.parse().unwrap();
+
+        let mut log = format!("{}\n", game);
+        game.simulate(1, |state, events| {
+            log += &format!("{}\n{}", format_events(events), state);
+        });
+        assert_eq!(log, EXAMPLE_ROUND);
+    }
+
+    #[test]
+    fn generated_groups_round_trip() {
+        let defenses = [
+            "",
+            "(weak to fire) ",
+            "(immune to cold, radiation) ",
+            "(3x damage from slashing) ",
+            "(immune to fire; weak to bludgeoning, slashing) ",
+            "(weak to cold; 4x damage from fire, radiation; immune to slashing) ",
+            "(5x damage from cold; 3x damage from fire; weak to acid) ",
+            "(1x damage from fire) ",
+            "(weak to fire; 2x damage from cold; immune to acid; 0x damage from slashing) ",
+        ];
+        let types = ["fire", "cold", "slashing", "acid"];
+
+        let mut lines = Vec::new();
+        for (i, d) in defenses.iter().enumerate() {
+            lines.push(format!(
+                "{} units each with {} hit points {}with an attack that does {} {} damage at initiative {}",
+                10 * i + 1,
+                100 + 7 * i,
+                d,
+                3 + i,
+                types[i % types.len()],
+                i + 1
+            ));
+        }
+        let (immune, infection) = lines.split_at(lines.len() / 2);
+        assert_round_trip(&format!(
+            "Immune System:\n{}\n\nInfection:\n{}",
+            immune.join("\n"),
+            infection.join("\n")
+        ));
+    }
 }
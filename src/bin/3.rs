@@ -1,7 +1,7 @@
 extern crate failure;
 extern crate regex;
 
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashMap};
 use std::env;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
@@ -9,6 +9,7 @@ use std::io::{BufRead, BufReader};
 use failure::{format_err, Error};
 use regex::Regex;
 
+#[derive(Debug, Copy, Clone)]
 struct Patch {
     id: usize,
     left: usize,
@@ -17,11 +18,293 @@ struct Patch {
     height: usize,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
-enum Status {
-    Unclaimed,
-    Claimed(usize),
-    Conflict,
+impl Patch {
+    fn right(&self) -> usize {
+        self.left + self.width
+    }
+
+    fn bottom(&self) -> usize {
+        self.top + self.height
+    }
+
+    fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+}
+
+#[derive(Debug, Default, Copy, Clone)]
+struct SweepNode {
+    // Active claims covering this node's whole span that weren't split further down.
+    cover: i32,
+    // Deepest coverage anywhere in the span, and the length covered at least once and twice.
+    max: i32,
+    once: u64,
+    twice: u64,
+    // Latest insertion to cover the whole span, and the latest to touch any of it.
+    stamp: usize,
+    latest: usize,
+}
+
+// Segment tree over the compressed y coordinates of the claims; leaf i covers ys[i]..ys[i + 1].
+struct SweepTree {
+    ys: Vec<usize>,
+    nodes: Vec<SweepNode>,
+}
+
+impl SweepTree {
+    fn new(ys: Vec<usize>) -> SweepTree {
+        let leaves = std::cmp::max(ys.len(), 2) - 1;
+        SweepTree {
+            ys,
+            nodes: vec![Default::default(); 4 * leaves],
+        }
+    }
+
+    fn leaves(&self) -> usize {
+        self.ys.len() - 1
+    }
+
+    fn span(&self, top: usize, bottom: usize) -> (usize, usize) {
+        (
+            self.ys.binary_search(&top).unwrap(),
+            self.ys.binary_search(&bottom).unwrap(),
+        )
+    }
+
+    fn pull(&mut self, node: usize, l: usize, r: usize) {
+        let len = (self.ys[r] - self.ys[l]) as u64;
+        let (max, once, twice) = if r - l == 1 {
+            (0, 0, 0)
+        } else {
+            let (a, b) = (self.nodes[2 * node], self.nodes[2 * node + 1]);
+            (
+                std::cmp::max(a.max, b.max),
+                a.once + b.once,
+                a.twice + b.twice,
+            )
+        };
+
+        let n = &mut self.nodes[node];
+        n.max = n.cover + max;
+        n.once = if n.cover >= 1 { len } else { once };
+        n.twice = match n.cover {
+            0 => twice,
+            1 => once,
+            _ => len,
+        };
+    }
+
+    fn add(&mut self, lo: usize, hi: usize, delta: i32, stamp: usize) {
+        let leaves = self.leaves();
+        self.add_in(1, 0, leaves, (lo, hi), delta, stamp);
+    }
+
+    fn add_in(
+        &mut self,
+        node: usize,
+        l: usize,
+        r: usize,
+        span: (usize, usize),
+        delta: i32,
+        stamp: usize,
+    ) {
+        let (lo, hi) = span;
+        if hi <= l || r <= lo {
+            return;
+        }
+
+        if lo <= l && r <= hi {
+            self.nodes[node].cover += delta;
+            self.nodes[node].stamp = std::cmp::max(self.nodes[node].stamp, stamp);
+        } else {
+            let mid = (l + r) / 2;
+            self.add_in(2 * node, l, mid, span, delta, stamp);
+            self.add_in(2 * node + 1, mid, r, span, delta, stamp);
+        }
+
+        self.pull(node, l, r);
+        let latest = if r - l == 1 {
+            0
+        } else {
+            std::cmp::max(self.nodes[2 * node].latest, self.nodes[2 * node + 1].latest)
+        };
+        let n = &mut self.nodes[node];
+        n.latest = std::cmp::max(n.stamp, latest);
+    }
+
+    // Deepest coverage and latest insertion stamp anywhere in leaves lo..hi.
+    fn query(&self, lo: usize, hi: usize) -> (i32, usize) {
+        self.query_in(1, 0, self.leaves(), lo, hi)
+    }
+
+    fn query_in(&self, node: usize, l: usize, r: usize, lo: usize, hi: usize) -> (i32, usize) {
+        if hi <= l || r <= lo {
+            return (0, 0);
+        }
+
+        let n = &self.nodes[node];
+        if lo <= l && r <= hi {
+            return (n.max, n.latest);
+        }
+
+        let mid = (l + r) / 2;
+        let a = self.query_in(2 * node, l, mid, lo, hi);
+        let b = self.query_in(2 * node + 1, mid, r, lo, hi);
+        (
+            n.cover + std::cmp::max(a.0, b.0),
+            std::cmp::max(n.stamp, std::cmp::max(a.1, b.1)),
+        )
+    }
+
+    fn covered_twice(&self) -> u64 {
+        self.nodes[1].twice
+    }
+
+    // Maximal runs of the sweep line covered by the same number of claims, as (top, bottom,
+    // depth), skipping the uncovered ones. Spans with nothing split below them come out whole.
+    fn runs(&self) -> Vec<(usize, usize, i32)> {
+        let mut runs = Vec::new();
+        self.runs_in(1, 0, self.leaves(), 0, &mut runs);
+        runs
+    }
+
+    fn runs_in(
+        &self,
+        node: usize,
+        l: usize,
+        r: usize,
+        above: i32,
+        runs: &mut Vec<(usize, usize, i32)>,
+    ) {
+        let n = &self.nodes[node];
+        let depth = above + n.cover;
+        if r - l > 1 && n.max > n.cover {
+            let mid = (l + r) / 2;
+            self.runs_in(2 * node, l, mid, depth, runs);
+            self.runs_in(2 * node + 1, mid, r, depth, runs);
+        } else if depth > 0 {
+            match runs.last_mut() {
+                Some(run) if run.1 == self.ys[l] && run.2 == depth => run.1 = self.ys[r],
+                _ => runs.push((self.ys[l], self.ys[r], depth)),
+            }
+        }
+    }
+}
+
+// The compressed y coordinates of every claim's edges, and the sweep's events as
+// (x, is_insertion, patch), sorted so that removals come ahead of insertions at the same x.
+fn sweep_events(patches: &[Patch]) -> (Vec<usize>, Vec<(usize, bool, usize)>) {
+    let mut ys: Vec<usize> = patches
+        .iter()
+        .filter(|p| !p.is_empty())
+        .flat_map(|p| vec![p.top, p.bottom()])
+        .collect();
+    ys.sort_unstable();
+    ys.dedup();
+
+    let mut events: Vec<(usize, bool, usize)> = Vec::with_capacity(2 * patches.len());
+    for (i, p) in patches.iter().enumerate().filter(|(_, p)| !p.is_empty()) {
+        events.push((p.left, true, i));
+        events.push((p.right(), false, i));
+    }
+    events.sort_unstable();
+
+    (ys, events)
+}
+
+struct Overlaps {
+    area: u64,
+    clean: Vec<usize>,
+}
+
+// Sweeps a vertical line across the fabric, adding each claim's y-span to the tree at its left
+// edge and removing it at its right. A claim overlaps another exactly when something is already
+// under it as it's added, or something else gets added over it before it's removed.
+fn find_overlaps(patches: &[Patch]) -> Overlaps {
+    let (ys, events) = sweep_events(patches);
+    let mut tree = SweepTree::new(ys);
+    let mut dirty = vec![false; patches.len()];
+    let mut stamps = vec![0; patches.len()];
+
+    let mut area = 0;
+    let mut prev_x = events.first().map_or(0, |e| e.0);
+    for (t, &(x, insertion, i)) in events.iter().enumerate() {
+        area += (x - prev_x) as u64 * tree.covered_twice();
+        prev_x = x;
+
+        let (lo, hi) = tree.span(patches[i].top, patches[i].bottom());
+        let (depth, latest) = tree.query(lo, hi);
+        if insertion {
+            dirty[i] |= depth > 0;
+            stamps[i] = t + 1;
+            tree.add(lo, hi, 1, t + 1);
+        } else {
+            dirty[i] |= latest > stamps[i];
+            tree.add(lo, hi, -1, 0);
+        }
+    }
+
+    Overlaps {
+        area,
+        clean: patches
+            .iter()
+            .zip(dirty.iter())
+            .filter(|(_, &dirty)| !dirty)
+            .map(|(p, _)| p.id)
+            .collect(),
+    }
+}
+
+// A rectangle of fabric, right and bottom edges excluded, covered by `depth` claims throughout.
+#[derive(Debug, Copy, Clone)]
+struct Region {
+    left: usize,
+    right: usize,
+    top: usize,
+    bottom: usize,
+    depth: i32,
+}
+
+// Every claimed bit of fabric, cut into rectangles of even depth by the same sweep as
+// `find_overlaps`. Each strip between events is read off the tree as runs of equal depth, and a
+// run that carries on unchanged from the strip before just widens the region it started.
+fn depth_map(patches: &[Patch]) -> Vec<Region> {
+    let (ys, events) = sweep_events(patches);
+    let mut tree = SweepTree::new(ys);
+
+    let mut regions: Vec<Region> = Vec::new();
+    let mut open: HashMap<(usize, usize, i32), usize> = HashMap::new();
+    let mut prev_x = events.first().map_or(0, |e| e.0);
+    for &(x, insertion, i) in events.iter() {
+        if x > prev_x {
+            let mut still_open = HashMap::new();
+            for run in tree.runs() {
+                let (top, bottom, depth) = run;
+                let region = match open.remove(&run) {
+                    Some(region) => region,
+                    None => {
+                        regions.push(Region {
+                            left: prev_x,
+                            right: prev_x,
+                            top,
+                            bottom,
+                            depth,
+                        });
+                        regions.len() - 1
+                    }
+                };
+                regions[region].right = x;
+                still_open.insert(run, region);
+            }
+            open = still_open;
+            prev_x = x;
+        }
+
+        let (lo, hi) = tree.span(patches[i].top, patches[i].bottom());
+        tree.add(lo, hi, if insertion { 1 } else { -1 }, 0);
+    }
+
+    regions
 }
 
 fn main() -> Result<(), Error> {
@@ -45,41 +328,26 @@ fn main() -> Result<(), Error> {
         .map(|res: Result<Patch, Error>| res.expect("failed to parse patch"))
         .collect();
 
-    let mut fabric = vec![vec![Status::Unclaimed; 1000]; 1000];
-    let mut conflicting = HashSet::new();
-    for patch in patches.iter() {
-        for x in patch.left..patch.left + patch.width {
-            for y in patch.top..patch.top + patch.height {
-                fabric[x][y] = match fabric[x][y] {
-                    Status::Unclaimed => Status::Claimed(patch.id),
-                    Status::Claimed(first) => {
-                        conflicting.insert(first);
-                        conflicting.insert(patch.id);
-                        Status::Conflict
-                    }
-                    Status::Conflict => {
-                        conflicting.insert(patch.id);
-                        Status::Conflict
-                    }
-                }
-            }
+    let overlaps = find_overlaps(&patches);
+    println!("cells in conflict: {}", overlaps.area);
+    println!("clean claims: {:?}", overlaps.clean);
+
+    // --depth lists where the claims are, and how many deep, followed by the area at each depth.
+    if args.iter().skip(2).any(|arg| arg == "--depth") {
+        let regions = depth_map(&patches);
+        let mut histogram = BTreeMap::new();
+        for r in regions.iter() {
+            let (width, height) = (r.right - r.left, r.bottom - r.top);
+            println!(
+                "{},{}: {}x{} covered by {} claims",
+                r.left, r.top, width, height, r.depth
+            );
+            *histogram.entry(r.depth).or_insert(0) += (width * height) as u64;
+        }
+        for (depth, area) in histogram {
+            println!("covered by {} claims: {}", depth, area);
         }
     }
 
-    let in_conflict: usize = fabric
-        .iter()
-        .map(|row| row.iter().filter(|&&s| s == Status::Conflict).count())
-        .sum();
-    println!("cells in conflict: {}", in_conflict);
-
-    println!(
-        "clean claims: {:?}",
-        patches
-            .iter()
-            .map(|patch| patch.id)
-            .filter(|id| !conflicting.contains(id))
-            .collect::<Vec<usize>>()
-    );
-
     Ok(())
 }
@@ -1,138 +1,637 @@
 extern crate failure;
 extern crate regex;
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::env;
+use std::fmt;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::prelude::*;
 
 use failure::{bail, ensure, format_err, Error};
 use regex::Regex;
 
+const MINUTES_PER_DAY: i64 = 24 * 60;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+struct Date {
+    year: i64,
+    month: u32,
+    day: u32,
+}
+
+impl Date {
+    // Days since 1970-01-01 in the proleptic Gregorian calendar.
+    fn days(&self) -> i64 {
+        let month = i64::from(self.month);
+        let year = if month <= 2 { self.year - 1 } else { self.year };
+        let era = if year >= 0 { year } else { year - 399 } / 400;
+        let year_of_era = year - era * 400;
+        let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5
+            + i64::from(self.day)
+            - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        era * 146_097 + day_of_era - 719_468
+    }
+
+    fn from_days(days: i64) -> Date {
+        let days = days + 719_468;
+        let era = if days >= 0 { days } else { days - 146_096 } / 146_097;
+        let day_of_era = days - era * 146_097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+        let month = if shifted_month < 10 {
+            shifted_month + 3
+        } else {
+            shifted_month - 9
+        } as u32;
+        Date {
+            year: year_of_era + era * 400 + i64::from(month <= 2),
+            month,
+            day,
+        }
+    }
+
+    fn is_valid(&self) -> bool {
+        Date::from_days(self.days()) == *self
+    }
+
+    fn next(&self) -> Date {
+        Date::from_days(self.days() + 1)
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+struct Timestamp {
+    date: Date,
+    hour: u32,
+    minute: u32,
+}
+
+impl Timestamp {
+    // Minutes since 1970-01-01 00:00, so that naps can be measured across midnight.
+    fn minutes(&self) -> i64 {
+        self.date.days() * MINUTES_PER_DAY + i64::from(self.hour * 60 + self.minute)
+    }
+
+    fn minute_of_day(&self) -> u32 {
+        self.hour * 60 + self.minute
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {:02}:{:02}", self.date, self.hour, self.minute)
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq)]
 enum Event {
-    ShiftChange { new_guard: usize },
-    FallsAsleep { minute: usize },
-    WakesUp { minute: usize },
+    ShiftChange { guard: usize },
+    FallsAsleep,
+    WakesUp,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
-enum Status {
-    Awake(usize),
-    Sleeping(usize, usize),
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Event::ShiftChange { guard } => write!(f, "Guard #{} begins shift", guard),
+            Event::FallsAsleep => f.write_str("falls asleep"),
+            Event::WakesUp => f.write_str("wakes up"),
+        }
+    }
 }
 
-fn main() -> Result<(), Error> {
-    let args: Vec<String> = env::args().collect();
-    let file = File::open(&args[1])?;
+#[derive(Clone, Debug)]
+struct Entry {
+    // 1-based line of the entry in the original, unsorted log, and the line itself.
+    line: usize,
+    text: String,
+    time: Timestamp,
+    event: Event,
+}
 
-    let mut entries: Vec<String> = BufReader::new(file)
-        .lines()
-        .map(|l| l.expect("file read failed"))
-        .collect();
-    entries.sort();
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{}] {}", self.time, self.event)
+    }
+}
 
+fn entry_pattern() -> Result<Regex, Error> {
     let date = r"(?P<year>\d{4})-(?P<month>\d{2})-(?P<day>\d{2})";
     let time = r"(?P<hour>\d{2}):(?P<minute>\d{2})";
     let shift_start = r"(Guard #(?P<id>\d+) begins shift)";
     let asleep = r"(?P<sleep>falls asleep)";
     let awake = r"(?P<wake>wakes up)";
     let full_pattern = format!(
-        r"\[{} {}\] ({}|{}|{})",
+        r"^\[{} {}\] ({}|{}|{})$",
         date, time, shift_start, asleep, awake
     );
-    let event = Regex::new(&full_pattern)?;
-
-    let events: Vec<Event> = entries
-        .iter()
-        .map(|entry| {
-            let captures = event
-                .captures(&entry)
-                .ok_or(format_err!("event pattern failed: {}", entry))?;
-            if let Some(id) = captures.name("id") {
-                Ok(Event::ShiftChange {
-                    new_guard: id.as_str().parse::<usize>()?,
-                })
-            } else {
-                let hour = captures
-                    .name("hour")
-                    .ok_or(format_err!("no hour"))?
-                    .as_str();
-                ensure!(hour == "00", "event outside midnight hour");
-
-                let minute = captures
-                    .name("minute")
-                    .ok_or(format_err!("no minute"))?
-                    .as_str()
-                    .parse::<usize>()?;
-
-                if let Some(_) = captures.name("sleep") {
-                    Ok(Event::FallsAsleep { minute })
-                } else if let Some(_) = captures.name("wake") {
-                    Ok(Event::WakesUp { minute })
-                } else {
-                    bail!("pattern match fail");
-                }
+    Ok(Regex::new(&full_pattern)?)
+}
+
+fn parse_entry(pattern: &Regex, line: usize, text: &str) -> Result<Entry, Error> {
+    let captures = pattern
+        .captures(text)
+        .ok_or_else(|| format_err!("event pattern failed"))?;
+
+    let time = Timestamp {
+        date: Date {
+            year: captures["year"].parse()?,
+            month: captures["month"].parse()?,
+            day: captures["day"].parse()?,
+        },
+        hour: captures["hour"].parse()?,
+        minute: captures["minute"].parse()?,
+    };
+    ensure!(
+        time.date.is_valid() && time.hour < 24 && time.minute < 60,
+        "bad timestamp"
+    );
+
+    let event = if let Some(id) = captures.name("id") {
+        Event::ShiftChange {
+            guard: id.as_str().parse()?,
+        }
+    } else if captures.name("sleep").is_some() {
+        Event::FallsAsleep
+    } else {
+        Event::WakesUp
+    };
+
+    Ok(Entry {
+        line,
+        text: text.to_string(),
+        time,
+        event,
+    })
+}
+
+// Non-blank lines of the log, numbered from 1.
+fn log_lines<'a>(input: &'a str) -> impl Iterator<Item = (usize, &'a str)> + 'a {
+    input
+        .lines()
+        .enumerate()
+        .map(|(i, l)| (i + 1, l.trim()))
+        .filter(|(_, l)| !l.is_empty())
+}
+
+// Ties keep the order they had in the log.
+fn sort_entries(entries: &mut [Entry]) {
+    entries.sort_by_key(|entry| (entry.time, entry.line));
+}
+
+fn parse_entries(input: &str) -> Result<Vec<Entry>, Error> {
+    let pattern = entry_pattern()?;
+    let mut entries = Vec::new();
+    for (line, text) in log_lines(input) {
+        let entry = parse_entry(&pattern, line, text)
+            .map_err(|e| format_err!("line {}: {}: {}", line, e, text))?;
+        entries.push(entry);
+    }
+
+    sort_entries(&mut entries);
+    Ok(entries)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Problem {
+    Malformed(String),
+    // Another entry has the same timestamp, so their order is a guess.
+    DuplicateTimestamp {
+        first: usize,
+    },
+    NoGuardOnDuty,
+    WakeWhileAwake,
+    // The guard fell asleep again without waking up from the nap started on line `asleep`.
+    MissingWake {
+        asleep: usize,
+    },
+    // The nap starting here never ends before the next shift, or the end of the log.
+    AsleepAtShiftEnd {
+        guard: usize,
+        shift_end: Option<usize>,
+    },
+    // Another shift already covers the same midnight hour.
+    OverlappingShift {
+        guard: usize,
+        other: usize,
+    },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::Malformed(e) => write!(f, "malformed entry ({})", e),
+            Problem::DuplicateTimestamp { first } => {
+                write!(f, "same timestamp as line {}", first)
             }
+            Problem::NoGuardOnDuty => f.write_str("nobody is on duty yet"),
+            Problem::WakeWhileAwake => f.write_str("wakes up without having fallen asleep"),
+            Problem::MissingWake { asleep } => write!(
+                f,
+                "falls asleep again without waking up from line {}",
+                asleep
+            ),
+            Problem::AsleepAtShiftEnd {
+                guard,
+                shift_end: Some(end),
+            } => write!(
+                f,
+                "guard #{} is still asleep when the shift ends on line {}",
+                guard, end
+            ),
+            Problem::AsleepAtShiftEnd {
+                guard,
+                shift_end: None,
+            } => write!(f, "guard #{} is still asleep when the log ends", guard),
+            Problem::OverlappingShift { guard, other } => write!(
+                f,
+                "guard #{} is on duty for the same night as line {}",
+                guard, other
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Anomaly {
+    line: usize,
+    text: String,
+    problem: Problem,
+}
+
+impl fmt::Display for Anomaly {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}: {}", self.line, self.problem, self.text)
+    }
+}
+
+// Runs the same state machine as `SleepLog::new`, but carries on past every problem so that all
+// of them are reported, in line order.
+fn lint(input: &str) -> Result<Vec<Anomaly>, Error> {
+    let pattern = entry_pattern()?;
+    let mut anomalies = Vec::new();
+    let mut report = |line: usize, text: &str, problem: Problem| {
+        anomalies.push(Anomaly {
+            line,
+            text: text.to_string(),
+            problem,
         })
-        .map(|res: Result<Event, Error>| res.expect("failed to parse event"))
-        .collect();
+    };
 
-    let mut status = Status::Awake(0);
-    let mut time_slept = HashMap::new();
-    let mut minutes_slept = HashMap::new();
-    for event in events {
-        status = match (status, event) {
-            (Status::Awake(_), Event::ShiftChange { new_guard }) => Status::Awake(new_guard),
-            (Status::Awake(id), Event::FallsAsleep { minute }) => Status::Sleeping(id, minute),
-            (Status::Sleeping(id, start), Event::WakesUp { minute }) => {
-                ensure!(id != 0, "bad guard id");
-                ensure!(minute > start, "inconsistent time");
-                *time_slept.entry(id).or_insert(0) += minute - start;
-                for m in start..minute {
-                    *minutes_slept
-                        .entry(id)
-                        .or_insert(HashMap::new())
-                        .entry(m)
-                        .or_insert(0) += 1;
+    let mut entries = Vec::new();
+    for (line, text) in log_lines(input) {
+        match parse_entry(&pattern, line, text) {
+            Ok(entry) => entries.push(entry),
+            Err(e) => report(line, text, Problem::Malformed(e.to_string())),
+        }
+    }
+    sort_entries(&mut entries);
+
+    let mut on_duty: Option<usize> = None;
+    let mut asleep: Option<&Entry> = None;
+    let mut nights: HashMap<Date, &Entry> = HashMap::new();
+    let mut first_at_time: Option<&Entry> = None;
+    for entry in entries.iter() {
+        match first_at_time {
+            Some(first) if first.time == entry.time => report(
+                entry.line,
+                &entry.text,
+                Problem::DuplicateTimestamp { first: first.line },
+            ),
+            _ => first_at_time = Some(entry),
+        }
+
+        match entry.event {
+            Event::ShiftChange { guard } => {
+                if let (Some(nap), Some(sleeper)) = (asleep.take(), on_duty) {
+                    report(
+                        nap.line,
+                        &nap.text,
+                        Problem::AsleepAtShiftEnd {
+                            guard: sleeper,
+                            shift_end: Some(entry.line),
+                        },
+                    );
+                }
+
+                let night = Shift::new(guard, entry.time).date;
+                match nights.get(&night) {
+                    Some(other) => report(
+                        entry.line,
+                        &entry.text,
+                        Problem::OverlappingShift {
+                            guard,
+                            other: other.line,
+                        },
+                    ),
+                    None => {
+                        nights.insert(night, entry);
+                    }
+                }
+                on_duty = Some(guard);
+            }
+            Event::FallsAsleep => match (on_duty, asleep) {
+                (None, _) => report(entry.line, &entry.text, Problem::NoGuardOnDuty),
+                (Some(_), Some(nap)) => {
+                    report(
+                        entry.line,
+                        &entry.text,
+                        Problem::MissingWake { asleep: nap.line },
+                    );
+                    asleep = Some(entry);
+                }
+                (Some(_), None) => asleep = Some(entry),
+            },
+            Event::WakesUp => {
+                if asleep.take().is_none() {
+                    report(entry.line, &entry.text, Problem::WakeWhileAwake);
                 }
-                Status::Awake(id)
             }
-            _ => bail!("bad transition (in state {:?}, got {:?})", status, event),
         }
     }
 
-    let guard = time_slept
-        .iter()
-        .max_by_key(|(_id, &slept)| slept)
-        .map(|(id, _slept)| id)
-        .unwrap();
+    if let (Some(nap), Some(guard)) = (asleep, on_duty) {
+        report(
+            nap.line,
+            &nap.text,
+            Problem::AsleepAtShiftEnd {
+                guard,
+                shift_end: None,
+            },
+        );
+    }
+
+    anomalies.sort_by_key(|anomaly| anomaly.line);
+    Ok(anomalies)
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Nap {
+    guard: usize,
+    start: Timestamp,
+    end: Timestamp,
+}
+
+impl Nap {
+    fn len(&self) -> i64 {
+        self.end.minutes() - self.start.minutes()
+    }
+
+    fn contains(&self, minutes: i64) -> bool {
+        self.start.minutes() <= minutes && minutes < self.end.minutes()
+    }
+
+    // Minute of the day for every minute of the nap, wrapping around midnight.
+    fn minutes_of_day(&self) -> impl Iterator<Item = u32> {
+        let start = self.start.minute_of_day();
+        (0..self.len()).map(move |m| ((i64::from(start) + m) % MINUTES_PER_DAY) as u32)
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Shift {
+    guard: usize,
+    // Guards who come on before noon are covering that day's midnight hour, the rest the next
+    // day's.
+    date: Date,
+    naps: Vec<Nap>,
+}
+
+impl Shift {
+    fn new(guard: usize, start: Timestamp) -> Shift {
+        let date = if start.hour < 12 {
+            start.date
+        } else {
+            start.date.next()
+        };
+        Shift {
+            guard,
+            date,
+            naps: Vec::new(),
+        }
+    }
+
+    fn is_asleep(&self, minutes: i64) -> bool {
+        self.naps.iter().any(|nap| nap.contains(minutes))
+    }
+}
+
+struct SleepLog {
+    shifts: Vec<Shift>,
+}
+
+impl SleepLog {
+    fn new(entries: &[Entry]) -> Result<SleepLog, Error> {
+        let mut shifts: Vec<Shift> = Vec::new();
+        let mut asleep: Option<Timestamp> = None;
+        for entry in entries {
+            match (entry.event, asleep, shifts.last_mut()) {
+                (Event::ShiftChange { guard }, None, _) => {
+                    shifts.push(Shift::new(guard, entry.time))
+                }
+                (Event::FallsAsleep, None, Some(_)) => asleep = Some(entry.time),
+                (Event::WakesUp, Some(start), Some(shift)) => {
+                    ensure!(
+                        entry.time > start,
+                        "line {}: woke up no later than falling asleep: {}",
+                        entry.line,
+                        entry
+                    );
+                    shift.naps.push(Nap {
+                        guard: shift.guard,
+                        start,
+                        end: entry.time,
+                    });
+                    asleep = None;
+                }
+                _ => bail!(
+                    "line {}: bad transition ({}asleep, got {})",
+                    entry.line,
+                    if asleep.is_some() { "" } else { "not " },
+                    entry
+                ),
+            }
+        }
+        ensure!(asleep.is_none(), "log ends with a guard still asleep");
+
+        Ok(SleepLog { shifts })
+    }
+
+    fn naps<'a>(&'a self) -> impl Iterator<Item = &'a Nap> + 'a {
+        self.shifts.iter().flat_map(|shift| shift.naps.iter())
+    }
+
+    fn time_slept(&self) -> BTreeMap<usize, i64> {
+        let mut time_slept = BTreeMap::new();
+        for shift in self.shifts.iter() {
+            let slept: i64 = shift.naps.iter().map(Nap::len).sum();
+            *time_slept.entry(shift.guard).or_insert(0) += slept;
+        }
+        time_slept
+    }
+
+    // For each guard, how many times they were asleep during each minute of the day.
+    fn histograms(&self) -> BTreeMap<usize, BTreeMap<u32, usize>> {
+        let mut histograms = BTreeMap::new();
+        for nap in self.naps() {
+            let histogram = histograms.entry(nap.guard).or_insert_with(BTreeMap::new);
+            for minute in nap.minutes_of_day() {
+                *histogram.entry(minute).or_insert(0) += 1;
+            }
+        }
+        histograms
+    }
+
+    fn histogram(&self, guard: usize) -> BTreeMap<u32, usize> {
+        let mut histogram = BTreeMap::new();
+        for nap in self.naps().filter(|nap| nap.guard == guard) {
+            for minute in nap.minutes_of_day() {
+                *histogram.entry(minute).or_insert(0) += 1;
+            }
+        }
+        histogram
+    }
+
+    // Each guard who was ever asleep at the given minute of the day, and how many times.
+    fn asleep_at(&self, minute: u32) -> BTreeMap<usize, usize> {
+        let mut guards = BTreeMap::new();
+        for nap in self.naps() {
+            let count = nap.minutes_of_day().filter(|&m| m == minute).count();
+            if count > 0 {
+                *guards.entry(nap.guard).or_insert(0) += count;
+            }
+        }
+        guards
+    }
+
+    fn longest_nap(&self) -> Option<&Nap> {
+        self.naps().max_by_key(|nap| nap.len())
+    }
+}
+
+// The puzzle's chart of each shift's midnight hour, one row per shift.
+impl fmt::Display for SleepLog {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let width = self
+            .shifts
+            .iter()
+            .map(|shift| format!("#{}", shift.guard).len())
+            .max()
+            .unwrap_or(0);
+
+        writeln!(f, "Date   {:<w$}  Minute", "ID", w = width)?;
+        let margin = " ".repeat(7 + width + 2);
+        let tens: String = (0..60).map(|m| (b'0' + m / 10) as char).collect();
+        let ones: String = (0..60).map(|m| (b'0' + m % 10) as char).collect();
+        writeln!(f, "{}{}", margin, tens)?;
+        writeln!(f, "{}{}", margin, ones)?;
+
+        for shift in self.shifts.iter() {
+            let midnight = shift.date.days() * MINUTES_PER_DAY;
+            let hour: String = (0..60)
+                .map(|m| {
+                    if shift.is_asleep(midnight + m) {
+                        '#'
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+            writeln!(
+                f,
+                "{:02}-{:02}  {:<w$}  {}",
+                shift.date.month,
+                shift.date.day,
+                format!("#{}", shift.guard),
+                hour,
+                w = width
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn main() -> Result<(), Error> {
+    let args: Vec<String> = env::args().collect();
+    let mut file = File::open(&args[1])?;
+    let mut input = String::new();
+    file.read_to_string(&mut input)?;
+
+    // --lint just reports everything suspicious about the log, including things the solver
+    // copes with, like ties that the log's own order settles.
+    if args.iter().skip(2).any(|arg| arg == "--lint") {
+        let anomalies = lint(&input)?;
+        for anomaly in anomalies.iter() {
+            println!("{}", anomaly);
+        }
+        println!("{} problems found", anomalies.len());
+        return Ok(());
+    }
+
+    let entries = parse_entries(&input)?;
+    let log = SleepLog::new(&entries)?;
+    if args.iter().skip(2).any(|arg| arg == "--timeline") {
+        print!("{}", log);
+    }
+
+    let (guard, _) = log
+        .time_slept()
+        .into_iter()
+        .filter(|&(_, slept)| slept > 0)
+        .max_by_key(|&(_, slept)| slept)
+        .ok_or_else(|| format_err!("nobody ever slept"))?;
     println!("sleepingest guard: {}", guard);
 
-    let minute = minutes_slept
-        .get(guard)
-        .unwrap()
-        .iter()
-        .max_by_key(|(_minute, slept)| *slept)
-        .map(|(minute, _slept)| minute)
-        .unwrap();
+    let (minute, _) = log
+        .histogram(guard)
+        .into_iter()
+        .max_by_key(|&(_, slept)| slept)
+        .ok_or_else(|| format_err!("guard {} never slept", guard))?;
     println!("guard {}'s sleepingest minute: {}", guard, minute);
-    println!("first answer: {}", guard * minute);
+    println!("first answer: {}", guard * minute as usize);
 
-    let (guard, minute, occurrences) = minutes_slept
-        .iter()
-        .map(|(guard, minutes)| {
-            let (minute, occurrences) = minutes.iter().max_by_key(|(_, &s)| s).unwrap();
-            (guard, minute, occurrences)
+    let (guard, minute, occurrences) = log
+        .histograms()
+        .into_iter()
+        .filter_map(|(guard, minutes)| {
+            let (minute, occurrences) = minutes.into_iter().max_by_key(|&(_, s)| s)?;
+            Some((guard, minute, occurrences))
         })
-        .max_by_key(|(_, _, &o)| o)
-        .unwrap();
+        .max_by_key(|&(_, _, o)| o)
+        .ok_or_else(|| format_err!("nobody ever slept"))?;
 
     println!(
         "guard {}'s slept most ({} times) on minute: {}",
         guard, occurrences, minute
     );
-    println!("second answer: {}", guard * minute);
+    println!("second answer: {}", guard * minute as usize);
+
+    let asleep: Vec<String> = log
+        .asleep_at(minute)
+        .into_iter()
+        .map(|(guard, times)| format!("#{} ({})", guard, times))
+        .collect();
+    println!("asleep on minute {}: {}", minute, asleep.join(", "));
+
+    if let Some(nap) = log.longest_nap() {
+        println!(
+            "longest nap: guard {} slept {} minutes from {} to {}",
+            nap.guard,
+            nap.len(),
+            nap.start,
+            nap.end
+        );
+    }
 
     Ok(())
 }
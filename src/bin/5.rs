@@ -2,92 +2,84 @@ extern crate failure;
 
 use std::env;
 use std::fs::File;
-use std::io::prelude::*;
+use std::io::{self, prelude::*, BufReader};
 
 use failure::Error;
 use rayon::prelude::*;
 
-fn as_lower(c: char) -> char {
-    let lower = c.to_lowercase().to_string();
-    let chars: Vec<char> = lower.chars().collect();
-    assert!(chars.len() == 1);
-    chars[0]
+fn reacts(a: u8, b: u8) -> bool {
+    a != b && a.eq_ignore_ascii_case(&b)
 }
 
-fn reacts(a: char, b: char) -> bool {
-    let a_lower = as_lower(a);
-    let b_lower = as_lower(b);
-    (a_lower == b_lower) && (a != b)
+// Units that have survived so far; each new unit either annihilates the top of the stack or
+// lands on it, so a single pass fully reacts the polymer.
+#[derive(Debug, Default)]
+struct Reducer {
+    stack: Vec<u8>,
 }
 
-fn react_once(units: &Vec<char>) -> (bool, Vec<char>) {
-    let mut annihilated: Vec<bool> = Vec::with_capacity(units.len());
-    annihilated.resize(units.len(), false);
-
-    let mut reacted = false;
-    units
-        .windows(2)
-        .enumerate()
-        .filter(|(_, pair)| reacts(pair[0], pair[1]))
-        .for_each(|(idx, _)| {
-            if !annihilated[idx] {
-                reacted = true;
-                annihilated[idx] = true;
-                annihilated[idx + 1] = true;
+impl Reducer {
+    fn push(&mut self, unit: u8) {
+        match self.stack.last() {
+            Some(&top) if reacts(top, unit) => {
+                self.stack.pop();
             }
-        });
+            _ => self.stack.push(unit),
+        }
+    }
+}
 
-    (
-        reacted,
-        units
-            .iter()
-            .enumerate()
-            .filter(|&(idx, _)| !annihilated[idx])
-            .map(|(_, &c)| c)
-            .collect(),
-    )
+fn react<I: IntoIterator<Item = u8>>(units: I) -> Vec<u8> {
+    let mut reducer = Reducer::default();
+    for unit in units {
+        reducer.push(unit);
+    }
+    reducer.stack
 }
 
-fn react_fully(input: &Vec<char>) -> Vec<char> {
-    let mut units = input.clone();
-    while let (true, result) = react_once(&units) {
-        units = result;
+// Reacts a polymer as it's read, so only the surviving units are ever held in memory.
+fn react_stream<R: Read>(input: R) -> io::Result<Vec<u8>> {
+    let mut reducer = Reducer::default();
+    for byte in BufReader::new(input).bytes() {
+        let unit = byte?;
+        if !unit.is_ascii_whitespace() {
+            reducer.push(unit);
+        }
     }
-    units
+    Ok(reducer.stack)
 }
 
 fn main() -> Result<(), Error> {
     let args: Vec<String> = env::args().collect();
-    let mut file = File::open(&args[1])?;
-
-    let mut polymer = String::new();
-    file.read_to_string(&mut polymer)?;
+    let file = File::open(&args[1])?;
 
-    let units: Vec<char> = polymer.trim().chars().collect();
-    println!("first answer: {}", react_fully(&units).len());
+    let reduced = react_stream(file)?;
+    println!("first answer: {}", reduced.len());
 
-    let mut types: Vec<char> = units.iter().map(|&c| as_lower(c)).collect();
+    // Removing a unit type and then reacting gives the same result whether or not the rest of
+    // the polymer reacted first, so start from the already reduced polymer.
+    let mut types: Vec<u8> = reduced.iter().map(|c| c.to_ascii_lowercase()).collect();
     types.sort();
     types.dedup();
 
-    let (t, shortest) = types
+    let best = types
         .par_iter()
         .map(|&t| {
-            println!("trying without {}", t);
-            let trial_units: Vec<char> = units
+            let trial = reduced
                 .iter()
-                .map(|&c| c)
-                .filter(|&c| as_lower(c) != t)
-                .collect();
-            (t, react_fully(&trial_units).len())
+                .cloned()
+                .filter(|c| c.to_ascii_lowercase() != t);
+            (t, react(trial).len())
         })
-        .min_by_key(|(_, n)| *n)
-        .unwrap();
+        .min_by_key(|(_, n)| *n);
 
-    println!(
-        "without {}, the shortest reaction was acheived ({})",
-        t, shortest
-    );
+    match best {
+        Some((t, shortest)) => println!(
+            "without {}, the shortest reaction was acheived ({})",
+            t as char, shortest
+        ),
+        None => println!("the polymer reacts away completely, so the shortest reaction is 0"),
+    }
 
     Ok(())
 }
@@ -1,123 +1,285 @@
 extern crate failure;
 extern crate regex;
 
-use std::collections::HashMap;
+use std::collections::VecDeque;
 use std::env;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
 
-use failure::Error;
+use failure::{ensure, format_err, Error};
 
 #[derive(Copy, Clone, Debug, PartialEq)]
 struct Coordinate {
-    x: i32,
-    y: i32,
+    x: i64,
+    y: i64,
 }
 
-#[derive(Copy, Clone, Debug)]
-struct Cell {
-    closest: usize,
-    distance: i32,
+// The smallest rectangle, edges included, holding every location.
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Bounds {
+    min: Coordinate,
+    max: Coordinate,
 }
 
-fn distance((x1, y1): (i32, i32), (x2, y2): (i32, i32)) -> i32 {
-    (x1 - x2).abs() + (y1 - y2).abs()
+impl Bounds {
+    fn of(points: &[Coordinate]) -> Option<Bounds> {
+        let first = *points.first()?;
+        Some(points.iter().fold(
+            Bounds {
+                min: first,
+                max: first,
+            },
+            |b, p| Bounds {
+                min: Coordinate {
+                    x: b.min.x.min(p.x),
+                    y: b.min.y.min(p.y),
+                },
+                max: Coordinate {
+                    x: b.max.x.max(p.x),
+                    y: b.max.y.max(p.y),
+                },
+            },
+        ))
+    }
+
+    fn width(&self) -> usize {
+        (self.max.x - self.min.x + 1) as usize
+    }
+
+    fn height(&self) -> usize {
+        (self.max.y - self.min.y + 1) as usize
+    }
+
+    fn index(&self, p: Coordinate) -> usize {
+        (p.y - self.min.y) as usize * self.width() + (p.x - self.min.x) as usize
+    }
+
+    fn coordinate(&self, index: usize) -> Coordinate {
+        Coordinate {
+            x: self.min.x + (index % self.width()) as i64,
+            y: self.min.y + (index / self.width()) as i64,
+        }
+    }
+
+    fn on_edge(&self, p: Coordinate) -> bool {
+        p.x == self.min.x || p.x == self.max.x || p.y == self.min.y || p.y == self.max.y
+    }
 }
 
-fn main() -> Result<(), Error> {
-    let args: Vec<String> = env::args().collect();
-    let file = File::open(&args[1])?;
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Owner {
+    Unreached,
+    Location(usize),
+    Tied,
+}
 
-    let locations: Vec<Coordinate> = BufReader::new(file)
-        .lines()
-        .map(|l| l.expect("file read failed"))
-        .map(|l| {
-            l.split(", ")
-                .map(|s| s.to_string())
-                .collect::<Vec<String>>()
-        })
-        .map(|parts| Coordinate {
-            x: parts[0].parse().unwrap(),
-            y: parts[1].parse().unwrap(),
-        })
-        .collect();
-
-    // Let's make a square grid large enough to hold all the locations.
-    let max_x = locations.iter().map(|l| l.x).max().unwrap();
-    let max_y = locations.iter().map(|l| l.y).max().unwrap();
-    let side = max_x.max(max_y) as usize;
-
-    let mut grid: Vec<Vec<Option<Cell>>> = vec![vec![None; side]; side];
-    let mut areas = HashMap::new();
-
-    // Naively blast over the grid and brute force the nearest point.
-    for x in 0..side {
-        for y in 0..side {
-            let cell = (x as i32, y as i32);
-            let distances: Vec<_> = locations
-                .iter()
-                .enumerate()
-                .map(|(index, coord)| (index, distance(cell, (coord.x, coord.y))))
-                .collect();
-            let (min_index, min_distance) = distances
-                .iter()
-                .min_by_key(|(_, dist)| dist)
-                .unwrap()
-                .clone();
-            if distances
-                .iter()
-                .filter(|(_, dist)| *dist == min_distance)
-                .count()
-                == 1
-            {
-                grid[x][y] = Some(Cell {
-                    closest: min_index,
-                    distance: min_distance,
-                });
-
-                *areas.entry(min_index).or_insert(0) += 1;
-            }
+impl Owner {
+    fn merge(self, other: Owner) -> Owner {
+        match (self, other) {
+            (Owner::Unreached, o) | (o, Owner::Unreached) => o,
+            (Owner::Location(a), Owner::Location(b)) if a == b => self,
+            _ => Owner::Tied,
         }
     }
+}
 
-    // Eliminate zones touching the edges.
-    for x in 0..side {
-        if let Some(Cell { closest, .. }) = grid[x][0] {
-            areas.remove(&closest);
+// Manhattan-distance Voronoi diagram of the locations, clipped to their bounding box.
+struct Voronoi {
+    bounds: Bounds,
+    owners: Vec<Owner>,
+    areas: Vec<usize>,
+    // A region that reaches the bounding box carries on forever: walking straight out from the
+    // box moves away from every location at the same rate, so the closest one never changes.
+    infinite: Vec<bool>,
+}
+
+impl Voronoi {
+    // Floods out from every location at once. The closest locations to a cell are exactly those
+    // closest to its neighbours one step nearer, so ties spread along with the regions.
+    fn new(locations: &[Coordinate]) -> Option<Voronoi> {
+        let bounds = Bounds::of(locations)?;
+        let mut owners = vec![Owner::Unreached; bounds.width() * bounds.height()];
+        let mut distances: Vec<Option<i64>> = vec![None; owners.len()];
+
+        let mut queue = VecDeque::new();
+        for (i, &location) in locations.iter().enumerate() {
+            let index = bounds.index(location);
+            owners[index] = owners[index].merge(Owner::Location(i));
+            if distances[index].is_none() {
+                distances[index] = Some(0);
+                queue.push_back(index);
+            }
         }
-        if let Some(Cell { closest, .. }) = grid[x][side - 1] {
-            areas.remove(&closest);
+
+        while let Some(index) = queue.pop_front() {
+            let p = bounds.coordinate(index);
+            let distance = distances[index].unwrap() + 1;
+            let neighbours = [(0, -1), (-1, 0), (1, 0), (0, 1)];
+            for &(dx, dy) in neighbours.iter() {
+                let n = Coordinate {
+                    x: p.x + dx,
+                    y: p.y + dy,
+                };
+                if n.x < bounds.min.x
+                    || n.x > bounds.max.x
+                    || n.y < bounds.min.y
+                    || n.y > bounds.max.y
+                {
+                    continue;
+                }
+
+                let next = bounds.index(n);
+                if distances[next].is_none() {
+                    distances[next] = Some(distance);
+                    queue.push_back(next);
+                }
+                if distances[next] == Some(distance) {
+                    owners[next] = owners[next].merge(owners[index]);
+                }
+            }
         }
+
+        let mut areas = vec![0; locations.len()];
+        let mut infinite = vec![false; locations.len()];
+        for (index, &owner) in owners.iter().enumerate() {
+            if let Owner::Location(i) = owner {
+                areas[i] += 1;
+                infinite[i] |= bounds.on_edge(bounds.coordinate(index));
+            }
+        }
+
+        Some(Voronoi {
+            bounds,
+            owners,
+            areas,
+            infinite,
+        })
+    }
+
+    fn largest_finite_region(&self) -> Option<(usize, usize)> {
+        self.areas
+            .iter()
+            .cloned()
+            .enumerate()
+            .filter(|&(i, _)| !self.infinite[i])
+            .max_by_key(|&(_, area)| area)
     }
-    for y in 0..side {
-        if let Some(Cell { closest, .. }) = grid[0][y] {
-            areas.remove(&closest);
+
+    // Binary PPM of the bounding box: each region gets its own colour, dimmed if it's infinite,
+    // with ties in black and the locations themselves in white.
+    fn write_ppm<W: Write>(&self, locations: &[Coordinate], out: &mut W) -> io::Result<()> {
+        write!(
+            out,
+            "P6\n{} {}\n255\n",
+            self.bounds.width(),
+            self.bounds.height()
+        )?;
+
+        let mut pixels = Vec::with_capacity(3 * self.owners.len());
+        for &owner in self.owners.iter() {
+            let rgb = match owner {
+                Owner::Location(i) => {
+                    let hash = (i as u32 + 1).wrapping_mul(2_654_435_761);
+                    let shade = if self.infinite[i] { 2 } else { 1 };
+                    [
+                        (0x40 | (hash >> 24) as u8) / shade,
+                        (0x40 | (hash >> 16) as u8) / shade,
+                        (0x40 | (hash >> 8) as u8) / shade,
+                    ]
+                }
+                Owner::Unreached | Owner::Tied => [0, 0, 0],
+            };
+            pixels.extend_from_slice(&rgb);
         }
-        if let Some(Cell { closest, .. }) = grid[side - 1][y] {
-            areas.remove(&closest);
+        for &location in locations {
+            let index = 3 * self.bounds.index(location);
+            pixels[index..index + 3].copy_from_slice(&[255, 255, 255]);
         }
+
+        out.write_all(&pixels)
     }
+}
 
-    let answer = areas.iter().max_by_key(|(_, &area)| area).unwrap();
-
-    println!("first answer: {}", answer.1);
-
-    // Do it again, but with total distance this time.
-    let mut area = 0;
-    for x in 0..side {
-        for y in 0..side {
-            let cell = (x as i32, y as i32);
-            let total_distance: i32 = locations
-                .iter()
-                .map(|coord| distance(cell, (coord.x, coord.y)))
-                .sum();
-            if total_distance < 10000 {
-                area += 1;
-            }
+// Total distance from a point to every location splits into the distance along each axis, so
+// list the per-axis totals that could possibly be under the limit and count the pairs whose sum
+// is. Past the bounding box each total grows by the number of locations per step, which keeps
+// the lists short however large the limit is.
+fn safe_region_size(locations: &[Coordinate], limit: i64) -> u64 {
+    let bounds = match Bounds::of(locations) {
+        Some(bounds) => bounds,
+        None => return 0,
+    };
+    let reach = limit / locations.len() as i64 + 1;
+
+    let totals = |lo: i64, hi: i64, axis: &dyn Fn(&Coordinate) -> i64| {
+        let mut totals: Vec<i64> = (lo - reach..=hi + reach)
+            .map(|v| locations.iter().map(|l| (axis(l) - v).abs()).sum())
+            .filter(|&total| total < limit)
+            .collect();
+        totals.sort_unstable();
+        totals
+    };
+    let xs = totals(bounds.min.x, bounds.max.x, &|l| l.x);
+    let ys = totals(bounds.min.y, bounds.max.y, &|l| l.y);
+
+    // For each x total, smallest first, count the y totals that still fit.
+    let mut count = 0;
+    let mut fits = ys.len();
+    for x in xs {
+        while fits > 0 && x + ys[fits - 1] >= limit {
+            fits -= 1;
         }
+        count += fits as u64;
+    }
+    count
+}
+
+fn main() -> Result<(), Error> {
+    let args: Vec<String> = env::args().collect();
+    let file = File::open(&args[1])?;
+
+    // Optional arguments: the total distance limit for the safe region, and --ppm <path> to
+    // write out a map of the regions.
+    let ppm = args[2..]
+        .iter()
+        .position(|a| a == "--ppm")
+        .map(|i| {
+            args.get(i + 3)
+                .ok_or_else(|| format_err!("--ppm needs a path"))
+        })
+        .transpose()?;
+    let limit = args[2..]
+        .iter()
+        .filter(|a| !a.starts_with("--") && Some(*a) != ppm)
+        .map(|a| a.parse())
+        .next()
+        .unwrap_or(Ok(10_000))?;
+
+    let locations = BufReader::new(file)
+        .lines()
+        .map(|l| {
+            let l = l?;
+            let mut parts = l.split(',').map(|s| s.trim().parse::<i64>());
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some(x), Some(y), None) => Ok(Coordinate { x: x?, y: y? }),
+                _ => Err(format_err!("bad location: {}", l)),
+            }
+        })
+        .collect::<Result<Vec<Coordinate>, Error>>()?;
+    ensure!(!locations.is_empty(), "no locations");
+
+    let voronoi = Voronoi::new(&locations).unwrap();
+    match voronoi.largest_finite_region() {
+        Some((_, area)) => println!("first answer: {}", area),
+        None => println!("every region is infinite"),
     }
 
-    println!("second answer: {}", area);
+    println!("second answer: {}", safe_region_size(&locations, limit));
+
+    if let Some(path) = ppm {
+        let mut out = BufWriter::new(File::create(path)?);
+        voronoi.write_ppm(&locations, &mut out)?;
+    }
 
     Ok(())
 }
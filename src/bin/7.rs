@@ -1,14 +1,15 @@
 use std::cmp::Ordering;
-use std::collections::{BinaryHeap, HashMap};
+use std::collections::{BinaryHeap, HashMap, HashSet};
 use std::env;
+use std::fmt;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 
-use failure::Error;
+use failure::{bail, ensure, format_err, Error};
 use regex::Regex;
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
-struct Name(char);
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Name(String);
 
 // Explicit (Partial)Ord impl so BinaryHeap is a min-heap.
 impl PartialOrd for Name {
@@ -23,9 +24,22 @@ impl Ord for Name {
     }
 }
 
+impl fmt::Display for Name {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
 impl Name {
-    fn work(&self) -> i32 {
-        61 + (self.0 as i32 - 'A' as i32)
+    // The puzzle's duration: `base` seconds plus 1 for A, 2 for B and so on. Longer names pay
+    // for each of their letters.
+    fn work(&self, base: u32) -> u32 {
+        base + self
+            .0
+            .chars()
+            .filter(|c| c.is_ascii_alphabetic())
+            .map(|c| c.to_ascii_uppercase() as u32 - 'A' as u32 + 1)
+            .sum::<u32>()
     }
 }
 
@@ -44,94 +58,285 @@ fn get_step(steps: &mut HashMap<Name, Step>, name: Name) -> &mut Step {
 
 fn build_ready_list(steps: &HashMap<Name, Step>) -> BinaryHeap<Name> {
     let mut ready = BinaryHeap::new();
-    for (&name, info) in steps.iter() {
+    for (name, info) in steps.iter() {
         if info.pending == 0 {
-            ready.push(name);
+            ready.push(name.clone());
         }
     }
     ready
 }
 
-fn first_answer(mut steps: HashMap<Name, Step>) {
-    let mut ready = build_ready_list(&steps);
-
-    // Run until we're out of steps.  Note that this assumes the puzzle input is reasonable.
-    let mut answer = String::new();
-    while let Some(step) = ready.pop() {
-        answer.push(step.0);
-        let unblocks = steps.get(&step).unwrap().feeds.clone();
-        for &name in unblocks.iter() {
-            let pending = &mut get_step(&mut steps, name).pending;
-            *pending -= 1;
-            if *pending == 0 {
-                ready.push(name);
-            }
+// Some step that never became ready; walk back through its unfinished prerequisites until one
+// repeats, which closes a loop.
+fn find_cycle(steps: &HashMap<Name, Step>, done: &HashSet<&Name>) -> Vec<Name> {
+    let mut prerequisite = HashMap::new();
+    for (name, step) in steps.iter().filter(|(name, _)| !done.contains(name)) {
+        for next in step.feeds.iter().filter(|next| !done.contains(next)) {
+            prerequisite.insert(next, name);
         }
     }
 
-    println!("first answer: {}", answer);
+    let mut at = match prerequisite.keys().min_by(|a, b| a.0.cmp(&b.0)) {
+        Some(&start) => start,
+        None => return Vec::new(),
+    };
+    let mut path: Vec<&Name> = Vec::new();
+    while !path.contains(&at) {
+        path.push(at);
+        at = prerequisite[at];
+    }
+
+    let start = path.iter().position(|&n| n == at).unwrap();
+    let mut cycle: Vec<Name> = path[start..].iter().rev().map(|&n| n.clone()).collect();
+    let first = cycle[0].clone();
+    cycle.push(first);
+    cycle
+}
+
+#[derive(Debug, Clone)]
+struct Task {
+    worker: usize,
+    name: Name,
+    start: u32,
+    end: u32,
 }
 
-fn second_answer(mut steps: HashMap<Name, Step>) {
-    let mut ready = build_ready_list(&steps);
-    let mut work = Vec::new();
+#[derive(Debug, Clone)]
+struct Schedule {
+    workers: usize,
+    tasks: Vec<Task>,
+    order: Vec<Name>,
+    time: u32,
+}
 
+fn schedule<F>(steps: &HashMap<Name, Step>, workers: usize, duration: F) -> Result<Schedule, Error>
+where
+    F: Fn(&Name) -> u32,
+{
+    ensure!(workers > 0, "need at least one worker");
+
+    let mut pending: HashMap<&Name, usize> =
+        steps.iter().map(|(name, s)| (name, s.pending)).collect();
+    let mut ready = build_ready_list(steps);
+    let mut busy: Vec<Option<(u32, Name)>> = vec![None; workers];
+
+    let mut tasks = Vec::new();
+    let mut order = Vec::new();
     let mut time = 0;
-    while !ready.is_empty() || !work.is_empty() {
+    loop {
         // Assign work to idle workers.
-        while !ready.is_empty() && work.len() < 5 {
-            let task = ready.pop().unwrap();
-            work.push((time + task.work(), task));
+        for (worker, slot) in busy.iter_mut().enumerate() {
+            if slot.is_none() {
+                if let Some(name) = ready.pop() {
+                    let end = time + duration(&name);
+                    tasks.push(Task {
+                        worker,
+                        name: name.clone(),
+                        start: time,
+                        end,
+                    });
+                    *slot = Some((end, name));
+                }
+            }
         }
 
         // Step until the next completed work.
-        work.sort_unstable_by_key(|(t, _)| -t);
-        if let Some((t_complete, _)) = work.last() {
-            time = *t_complete;
-        }
+        time = match busy.iter().filter_map(|s| s.as_ref().map(|s| s.0)).min() {
+            Some(t) => t,
+            None => break,
+        };
 
         // Figure out what's finished now.
-        while let Some((t_complete, name)) = work.last() {
-            if time >= *t_complete {
-                let unblocks = steps.get(name).unwrap().feeds.clone();
-                for &name in unblocks.iter() {
-                    let pending = &mut get_step(&mut steps, name).pending;
-                    *pending -= 1;
-                    if *pending == 0 {
-                        ready.push(name);
-                    }
+        let mut finished = Vec::new();
+        for slot in busy.iter_mut() {
+            if slot.as_ref().is_some_and(|s| s.0 == time) {
+                finished.push(slot.take().unwrap().1);
+            }
+        }
+        finished.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+
+        for name in finished {
+            for next in steps[&name].feeds.iter() {
+                let count = pending.get_mut(next).unwrap();
+                *count -= 1;
+                if *count == 0 {
+                    ready.push(next.clone());
                 }
-                work.pop();
-            } else {
-                // Not being able to combine while-let and a boolean check is lame.
-                break;
             }
+            order.push(name);
+        }
+    }
+
+    if order.len() < steps.len() {
+        let done: HashSet<&Name> = order.iter().collect();
+        let cycle: Vec<String> = find_cycle(steps, &done)
+            .iter()
+            .map(|n| n.to_string())
+            .collect();
+        bail!("dependency cycle: {}", cycle.join(" -> "));
+    }
+
+    Ok(Schedule {
+        workers,
+        tasks,
+        order,
+        time,
+    })
+}
+
+// Longest chain of step durations through the graph. No number of workers can finish sooner.
+fn critical_path<F>(steps: &HashMap<Name, Step>, duration: F) -> Result<(u32, Vec<Name>), Error>
+where
+    F: Fn(&Name) -> u32,
+{
+    let order = schedule(steps, 1, |_| 1)?.order;
+
+    // For each step, the earliest it can finish and the prerequisite holding it up longest.
+    let mut start: HashMap<&Name, (u32, Option<&Name>)> = HashMap::new();
+    let mut finish: HashMap<&Name, u32> = HashMap::new();
+    for name in order.iter() {
+        let (begin, _) = start.get(name).cloned().unwrap_or((0, None));
+        let end = begin + duration(name);
+        finish.insert(name, end);
+
+        for next in steps[name].feeds.iter() {
+            let entry = start.entry(next).or_insert((0, None));
+            if entry.1.is_none() || end > entry.0 {
+                *entry = (end, Some(name));
+            }
+        }
+    }
+
+    let last = match order.iter().max_by_key(|&n| finish[n]) {
+        Some(last) => last,
+        None => return Ok((0, Vec::new())),
+    };
+
+    let mut path = vec![last.clone()];
+    let mut at = last;
+    while let Some(&(_, Some(prev))) = start.get(at) {
+        path.push(prev.clone());
+        at = prev;
+    }
+    path.reverse();
+
+    Ok((finish[last], path))
+}
+
+fn to_dot(steps: &HashMap<Name, Step>) -> String {
+    let mut names: Vec<&Name> = steps.keys().collect();
+    names.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+
+    let mut dot = String::from("digraph steps {\n");
+    for name in names {
+        dot += &format!("    {:?};\n", name.0);
+
+        let mut feeds: Vec<&Name> = steps[name].feeds.iter().collect();
+        feeds.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        for next in feeds {
+            dot += &format!("    {:?} -> {:?};\n", name.0, next.0);
         }
     }
+    dot.push_str("}\n");
+    dot
+}
+
+// The per-second table from the puzzle text: what each worker is doing, and what's done.
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Second")?;
+        for worker in 1..=self.workers {
+            write!(f, "   Worker {}", worker)?;
+        }
+        writeln!(f, "   Done")?;
+
+        let mut completed: Vec<&Task> = self.tasks.iter().collect();
+        completed.sort_by(|a, b| a.end.cmp(&b.end).then(a.name.0.cmp(&b.name.0)));
+
+        for second in 0..=self.time {
+            write!(f, "{:>4}  ", second)?;
+            for worker in 0..self.workers {
+                let cell = self
+                    .tasks
+                    .iter()
+                    .find(|t| t.worker == worker && t.start <= second && second < t.end)
+                    .map_or(".".to_string(), |t| t.name.to_string());
+                write!(f, "   {:^8}", cell)?;
+            }
 
-    println!("second answer: {}", time);
+            let done: String = completed
+                .iter()
+                .take_while(|t| t.end <= second)
+                .map(|t| t.name.0.as_str())
+                .collect();
+            writeln!(f, "   {}", done)?;
+        }
+
+        Ok(())
+    }
 }
 
 fn main() -> Result<(), Error> {
     let args: Vec<String> = env::args().collect();
     let file = File::open(&args[1])?;
 
-    let re = Regex::new(r"Step ([A-Z]) must be finished before step ([A-Z]) can begin.")?;
+    // Optional arguments: worker count, base step duration, --chart to print the schedule and
+    // --dot to print the step graph for Graphviz.
+    let chart = args[2..].iter().any(|a| a == "--chart");
+    let dot = args[2..].iter().any(|a| a == "--dot");
+    let numbers = args[2..]
+        .iter()
+        .filter(|a| !a.starts_with("--"))
+        .map(|a| a.parse())
+        .collect::<Result<Vec<u32>, _>>()?;
+    let workers = numbers.first().map_or(5, |&n| n as usize);
+    let base = numbers.get(1).cloned().unwrap_or(60);
+
+    let re = Regex::new(r"Step (\S+) must be finished before step (\S+) can begin.")?;
 
     // Build the dependency graph.
     let mut steps = HashMap::new();
     for line in BufReader::new(file).lines() {
-        let line = line.unwrap();
-        let captures = re.captures(&line).unwrap();
-        let input = Name(captures[1].chars().next().unwrap());
-        let output = Name(captures[2].chars().next().unwrap());
+        let line = line?;
+        let captures = re
+            .captures(&line)
+            .ok_or_else(|| format_err!("bad input: {}", line))?;
+        let input = Name(captures[1].to_string());
+        let output = Name(captures[2].to_string());
 
-        get_step(&mut steps, input).feeds.push(output);
+        get_step(&mut steps, input).feeds.push(output.clone());
         get_step(&mut steps, output).pending += 1;
     }
 
-    first_answer(steps.clone());
-    second_answer(steps);
+    if dot {
+        print!("{}", to_dot(&steps));
+    }
+
+    // With a single worker the schedule just runs the steps in dependency order.
+    let order: Vec<String> = schedule(&steps, 1, |_| 1)?
+        .order
+        .iter()
+        .map(|n| n.to_string())
+        .collect();
+    println!("first answer: {}", order.concat());
+
+    let plan = schedule(&steps, workers, |name| name.work(base))?;
+    if chart {
+        print!("{}", plan);
+    }
+    println!("second answer: {}", plan.time);
+
+    let (length, path) = critical_path(&steps, |name| name.work(base))?;
+    let path: Vec<String> = path.iter().map(|n| n.to_string()).collect();
+    println!("critical path ({} seconds): {}", length, path.join(" -> "));
+    if plan.time == length {
+        println!("more workers would not finish any sooner");
+    } else {
+        println!(
+            "more workers could save up to {} seconds",
+            plan.time - length
+        );
+    }
 
     Ok(())
 }
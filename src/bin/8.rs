@@ -1,56 +1,170 @@
+use std::collections::HashMap;
 use std::env;
 use std::fs::File;
 use std::io::prelude::*;
 
 use failure::{ensure, Error, Fail};
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 struct Node {
     children: Vec<Node>,
     metadata: Vec<usize>,
 }
 
+// The default drop recurses once per level, so flatten the tree first.
+impl Drop for Node {
+    fn drop(&mut self) {
+        let mut doomed = std::mem::take(&mut self.children);
+        while let Some(mut node) = doomed.pop() {
+            doomed.append(&mut node.children);
+        }
+    }
+}
+
 #[derive(Fail, Debug)]
 #[fail(display = "Ran out of input parsing node")]
 struct EofError;
 
-fn parse_node(input: &[usize]) -> Result<(Node, &[usize]), Error> {
-    let (&n_children, input) = input.split_first().ok_or(EofError)?;
-    let (&n_metadata, mut input) = input.split_first().ok_or(EofError)?;
+// A node whose header has been read, still waiting on some of its children.
+struct Partial {
+    remaining: usize,
+    n_metadata: usize,
+    children: Vec<Node>,
+}
 
-    let mut children = Vec::new();
-    for _ in 0..n_children {
-        let (child, remain) = parse_node(input)?;
-        children.push(child);
+fn parse_node(mut input: &[usize]) -> Result<(Node, &[usize]), Error> {
+    let mut stack: Vec<Partial> = Vec::new();
+    loop {
+        let (&n_children, remain) = input.split_first().ok_or(EofError)?;
+        let (&n_metadata, remain) = remain.split_first().ok_or(EofError)?;
         input = remain;
-    }
+        stack.push(Partial {
+            remaining: n_children,
+            n_metadata,
+            children: Vec::new(),
+        });
 
-    let mut metadata = Vec::new();
-    for _ in 0..n_metadata {
-        let (&datum, remain) = input.split_first().ok_or(EofError)?;
-        metadata.push(datum);
-        input = remain;
+        // Close out every node that has all of its children, handing each to its parent.
+        while stack.last().is_some_and(|p| p.remaining == 0) {
+            let partial = stack.pop().unwrap();
+            if input.len() < partial.n_metadata {
+                return Err(EofError.into());
+            }
+            let (metadata, remain) = input.split_at(partial.n_metadata);
+            input = remain;
+
+            let node = Node {
+                children: partial.children,
+                metadata: metadata.to_vec(),
+            };
+            match stack.last_mut() {
+                Some(parent) => {
+                    parent.children.push(node);
+                    parent.remaining -= 1;
+                }
+                None => return Ok((node, input)),
+            }
+        }
     }
+}
+
+// Post-order walk with an explicit stack, calling `visit` with each node's checksum and value
+// once all of its children have been worked out. Returns the root's checksum and value.
+fn evaluate<F: FnMut(&Node, usize, usize)>(root: &Node, mut visit: F) -> (usize, usize) {
+    let mut stack: Vec<(&Node, Vec<(usize, usize)>)> = vec![(root, Vec::new())];
+    loop {
+        let (node, done) = {
+            let (node, results) = stack.last().unwrap();
+            (*node, results.len())
+        };
+        if done < node.children.len() {
+            stack.push((&node.children[done], Vec::new()));
+            continue;
+        }
+
+        let (node, results) = stack.pop().unwrap();
+        let own = node.metadata.iter().sum::<usize>();
+        let checksum = own + results.iter().map(|r| r.0).sum::<usize>();
 
-    Ok((Node { children, metadata }, input))
+        // If a node has no child nodes, its value is the sum of its metadata entries.
+        // Otherwise, the value is the sum of the referenced child nodes' values.
+        let value = if node.children.is_empty() {
+            own
+        } else {
+            node.metadata
+                .iter()
+                .filter(|&&datum| datum != 0)
+                .map(|datum| results.get(datum - 1).map_or(0, |r| r.1))
+                .sum::<usize>()
+        };
+
+        visit(node, checksum, value);
+        match stack.last_mut() {
+            Some((_, parent_results)) => parent_results.push((checksum, value)),
+            None => return (checksum, value),
+        }
+    }
 }
 
 fn checksum(node: &Node) -> usize {
-    node.metadata.iter().sum::<usize>() + node.children.iter().map(|n| checksum(&n)).sum::<usize>()
+    evaluate(node, |_, _, _| ()).0
 }
 
 fn value(node: &Node) -> usize {
-    // If a node has no child nodes, its value is the sum of its metadata entries.
-    if node.children.is_empty() {
-        return node.metadata.iter().sum::<usize>();
+    evaluate(node, |_, _, _| ()).1
+}
+
+fn pretty_print(root: &Node) -> String {
+    let mut summaries = HashMap::new();
+    evaluate(root, |node, checksum, value| {
+        summaries.insert(node as *const Node, (checksum, value));
+    });
+
+    // Each entry carries the prefix for its own line and the prefix for its children's lines.
+    let mut out = String::new();
+    let mut stack = vec![(root, String::new(), String::new())];
+    while let Some((node, line_prefix, child_prefix)) = stack.pop() {
+        let (checksum, value) = summaries[&(node as *const Node)];
+        out += &format!(
+            "{}checksum {}, value {}, metadata {:?}\n",
+            line_prefix, checksum, value, node.metadata
+        );
+
+        let n = node.children.len();
+        for (i, child) in node.children.iter().enumerate().rev() {
+            let last = i + 1 == n;
+            stack.push((
+                child,
+                format!("{}{}", child_prefix, if last { "└── " } else { "├── " }),
+                format!("{}{}", child_prefix, if last { "    " } else { "│   " }),
+            ));
+        }
+    }
+    out
+}
+
+fn encode(root: &Node) -> String {
+    enum Pending<'a> {
+        Node(&'a Node),
+        Metadata(&'a [usize]),
+    }
+
+    let mut numbers = Vec::new();
+    let mut stack = vec![Pending::Node(root)];
+    while let Some(pending) = stack.pop() {
+        match pending {
+            Pending::Node(node) => {
+                numbers.push(node.children.len());
+                numbers.push(node.metadata.len());
+                stack.push(Pending::Metadata(&node.metadata));
+                stack.extend(node.children.iter().rev().map(Pending::Node));
+            }
+            Pending::Metadata(metadata) => numbers.extend_from_slice(metadata),
+        }
     }
 
-    // Otherwise, the value is the sum of the referenced child nodes' values.
-    node.metadata
-        .iter()
-        .filter(|&&datum| datum != 0)
-        .map(|datum| node.children.get(datum - 1).map_or(0, |child| value(child)))
-        .sum::<usize>()
+    let numbers: Vec<String> = numbers.iter().map(|n| n.to_string()).collect();
+    numbers.join(" ")
 }
 
 fn main() -> Result<(), Error> {
@@ -60,15 +174,104 @@ fn main() -> Result<(), Error> {
     let mut input = String::new();
     file.read_to_string(&mut input)?;
     let input: Vec<usize> = input
-        .trim()
-        .split(' ')
+        .split_whitespace()
         .map(|s| s.parse().expect("non-integer input"))
         .collect();
 
     let (root, leftover) = parse_node(&input)?;
     ensure!(leftover.is_empty(), "had leftover data at root");
+
+    if args.iter().skip(2).any(|arg| arg == "--tree") {
+        print!("{}", pretty_print(&root));
+    }
+    if args.iter().skip(2).any(|arg| arg == "--encode") {
+        println!("{}", encode(&root));
+    }
+
     println!("first answer: {}", checksum(&root));
     println!("second answer: {}", value(&root));
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaf(metadata: Vec<usize>) -> Node {
+        Node {
+            children: Vec::new(),
+            metadata,
+        }
+    }
+
+    // A node per level, each with one child and a single metadata entry pointing at it.
+    fn chain(depth: usize) -> Node {
+        let mut node = leaf(vec![1]);
+        for _ in 1..depth {
+            node = Node {
+                children: vec![node],
+                metadata: vec![1],
+            };
+        }
+        node
+    }
+
+    // Bushy trees from a simple LCG, with metadata that sometimes points past the children.
+    fn generate(seed: &mut u64, depth: usize) -> Node {
+        let mut next = |n: u64| {
+            *seed = seed
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1_442_695_040_888_963_407);
+            ((*seed >> 33) % n) as usize
+        };
+        let n_children = if depth == 0 { 0 } else { next(4) };
+        let metadata = (0..next(4) + 1).map(|_| next(5)).collect();
+        let mut children = Vec::new();
+        for _ in 0..n_children {
+            children.push(generate(seed, depth - 1));
+        }
+        Node { children, metadata }
+    }
+
+    fn assert_round_trip(tree: &Node) {
+        let encoded = encode(tree);
+        let input: Vec<usize> = encoded
+            .split_whitespace()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        let (parsed, leftover) = parse_node(&input).unwrap();
+        assert!(leftover.is_empty());
+        assert_eq!(checksum(&parsed), checksum(tree));
+        assert_eq!(value(&parsed), value(tree));
+        assert_eq!(encode(&parsed), encoded);
+    }
+
+    #[test]
+    fn example_round_trips() {
+        let input = [2, 3, 0, 3, 10, 11, 12, 1, 1, 0, 1, 99, 2, 1, 1, 2];
+        let (root, leftover) = parse_node(&input).unwrap();
+        assert!(leftover.is_empty());
+        assert_eq!((checksum(&root), value(&root)), (138, 66));
+        assert_eq!(encode(&root), "2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2");
+        assert_round_trip(&root);
+    }
+
+    #[test]
+    fn generated_trees_round_trip() {
+        let mut seed = 8;
+        for depth in 0..6 {
+            for _ in 0..20 {
+                assert_round_trip(&generate(&mut seed, depth));
+            }
+        }
+        assert_round_trip(&leaf(Vec::new()));
+    }
+
+    #[test]
+    fn deep_chain_round_trips() {
+        let tree = chain(200_000);
+        assert_eq!((checksum(&tree), value(&tree)), (200_000, 1));
+        assert_round_trip(&tree);
+    }
+}
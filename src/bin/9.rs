@@ -1,68 +1,192 @@
-use std::collections::VecDeque;
+#[macro_use]
+extern crate lazy_static;
 
-use failure::Error;
+use std::env;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::str::FromStr;
 
-fn rotate_cw(circle: &mut VecDeque<usize>, n: usize) {
-    for _ in 0..n {
-        let marble = circle.pop_front().unwrap();
-        circle.push_back(marble);
+use failure::{ensure, format_err, Error};
+use regex::Regex;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Rules {
+    // Marbles numbered with a multiple of this are kept instead of placed...
+    special_multiple: usize,
+    // ...along with the marble this many steps counter-clockwise of the current one.
+    removal_offset: usize,
+}
+
+impl Default for Rules {
+    fn default() -> Rules {
+        Rules {
+            special_multiple: 23,
+            removal_offset: 7,
+        }
     }
 }
 
-fn rotate_ccw(circle: &mut VecDeque<usize>, n: usize) {
-    for _ in 0..n {
-        let marble = circle.pop_back().unwrap();
-        circle.push_front(marble);
+#[derive(Debug, Copy, Clone)]
+struct MarbleGame {
+    players: usize,
+    last_marble: usize,
+    rules: Rules,
+}
+
+impl FromStr for MarbleGame {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        lazy_static! {
+            static ref RE: Regex =
+                Regex::new(r"^(\d+) players; last marble is worth (\d+) points").unwrap();
+        }
+
+        let captures = RE
+            .captures(s)
+            .ok_or_else(|| format_err!("bad input: {}", s))?;
+
+        Ok(MarbleGame {
+            players: captures[1].parse()?,
+            last_marble: captures[2].parse()?,
+            rules: Default::default(),
+        })
     }
 }
 
-/// Run the game with @p players until marble @p last_marble.
-fn game_vec(players: usize, last_marble: usize) {
-    // 100x larger eh?  That's cute.
-    // We'll use a VecDeque and keep the "current" marble at index 0.
-    let mut circle: VecDeque<usize> = vec![0].into_iter().collect();
-    let mut next_marble = 1;
-    let mut scores = vec![0; players];
-    let mut player = 0;
-
-    while next_marble <= last_marble {
-        match next_marble % 23 {
-            0 => {
-                rotate_ccw(&mut circle, 6);
-                let removed = circle.pop_back().unwrap();
-                scores[player] += next_marble + removed;
-            }
-            _ => {
-                rotate_cw(&mut circle, 2);
-                circle.push_front(next_marble);
-            }
+impl MarbleGame {
+    fn validate(&self) -> Result<(), Error> {
+        ensure!(self.players > 0, "a game needs at least one player");
+        ensure!(
+            self.last_marble < u32::MAX as usize,
+            "too many marbles: {}",
+            self.last_marble
+        );
+        // Otherwise the very first special marble would take marble 0 and leave no circle.
+        ensure!(
+            self.rules.special_multiple >= 2,
+            "special marbles must be multiples of at least 2"
+        );
+        Ok(())
+    }
+
+    fn scaled(self, factor: usize) -> MarbleGame {
+        MarbleGame {
+            last_marble: self.last_marble * factor,
+            ..self
         }
-        next_marble += 1;
-        player = (player + 1) % players;
     }
 
-    let (winner, high_score) = scores
-        .iter()
-        .enumerate()
-        .max_by_key(|(_, &score)| score)
-        .unwrap();
-
-    println!(
-        "{} players; last marble is worth {} points: high score is {} [player {}]",
-        players,
-        last_marble,
-        high_score,
-        winner + 1
-    );
+    // Returns each player's final score. The circle is a doubly linked list threaded through
+    // two arrays indexed by marble number, allocated once up front at 8 bytes per marble.
+    fn play(&self) -> Result<Vec<u64>, Error> {
+        self.validate()?;
+
+        let mut clockwise: Vec<u32> = vec![0; self.last_marble + 1];
+        let mut counter_clockwise: Vec<u32> = vec![0; self.last_marble + 1];
+        let mut current = 0;
+        let mut scores = vec![0; self.players];
+
+        for marble in 1..=self.last_marble {
+            let player = (marble - 1) % self.players;
+
+            if marble % self.rules.special_multiple == 0 {
+                for _ in 0..self.rules.removal_offset {
+                    current = counter_clockwise[current] as usize;
+                }
+
+                let before = counter_clockwise[current];
+                let after = clockwise[current];
+                clockwise[before as usize] = after;
+                counter_clockwise[after as usize] = before;
+
+                scores[player] += (marble + current) as u64;
+                current = after as usize;
+            } else {
+                let before = clockwise[current] as usize;
+                let after = clockwise[before] as usize;
+                clockwise[before] = marble as u32;
+                counter_clockwise[marble] = before as u32;
+                clockwise[marble] = after as u32;
+                counter_clockwise[after] = marble as u32;
+
+                current = marble;
+            }
+        }
+
+        Ok(scores)
+    }
 }
 
 fn main() -> Result<(), Error> {
-    game_vec(10, 1618);
-    game_vec(13, 7999);
-    game_vec(17, 1104);
-    game_vec(21, 6111);
-    game_vec(30, 5807);
-    game_vec(468, 71843);
-    game_vec(468, 71843 * 100);
+    let args: Vec<String> = env::args().collect();
+    let file = File::open(&args[1])?;
+
+    // Any further arguments are factors to scale each game's last marble by, plus
+    // --special-multiple N and --removal-offset N to change the rules from the puzzle's.
+    let mut rules = Rules::default();
+    let mut factors = Vec::new();
+    let mut rest = args[2..].iter();
+    while let Some(arg) = rest.next() {
+        let setting = match arg.as_str() {
+            "--special-multiple" => &mut rules.special_multiple,
+            "--removal-offset" => &mut rules.removal_offset,
+            _ => {
+                factors.push(arg.parse()?);
+                continue;
+            }
+        };
+        *setting = rest
+            .next()
+            .ok_or_else(|| format_err!("{} needs a value", arg))?
+            .parse()?;
+    }
+    let factors = if factors.is_empty() {
+        vec![1, 100]
+    } else {
+        factors
+    };
+
+    let expected_re = Regex::new(r"high score is (\d+)")?;
+
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        let game = MarbleGame {
+            rules,
+            ..line.parse()?
+        };
+        let expected: Option<u64> = match expected_re.captures(&line) {
+            Some(captures) => Some(captures[1].parse()?),
+            None => None,
+        };
+
+        for &factor in factors.iter() {
+            let scaled = game.scaled(factor);
+            let scores = scaled.play()?;
+            let (winner, &high_score) = scores
+                .iter()
+                .enumerate()
+                .max_by_key(|(_, &score)| score)
+                .unwrap();
+
+            println!(
+                "{} players; last marble is worth {} points: high score is {} [player {}]",
+                scaled.players,
+                scaled.last_marble,
+                high_score,
+                winner + 1
+            );
+
+            // The expected scores are only for the puzzle's own rules.
+            if let (1, Some(expected), true) = (factor, expected, rules == Rules::default()) {
+                ensure!(
+                    high_score == expected,
+                    "expected a high score of {}",
+                    expected
+                );
+            }
+        }
+    }
+
     Ok(())
 }
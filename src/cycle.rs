@@ -0,0 +1,181 @@
+//! Cycle detection for simulations that eventually settle into repeating themselves.
+
+/// A sequence of states that, from step `start` on, repeats every `period` steps.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Cycle {
+    pub start: u64,
+    pub period: u64,
+}
+
+impl Cycle {
+    /// The earliest step whose state matches the one at step `n`.
+    pub fn reduce(&self, n: u64) -> u64 {
+        if n < self.start {
+            n
+        } else {
+            self.start + (n - self.start) % self.period
+        }
+    }
+}
+
+// Brent's algorithm: the tortoise waits at successive powers of two while the hare runs ahead,
+// so it only ever holds a couple of states, and they're compared in place rather than copied.
+// Gives up with the hare's state if it gets `limit` steps in without a repeat.
+fn brent<S, F, E>(start: &S, limit: u64, step: &mut F, same: &mut E) -> Result<Cycle, S>
+where
+    S: Clone,
+    F: FnMut(&S) -> S,
+    E: FnMut(&S, &S) -> bool,
+{
+    let mut power = 1;
+    let mut period = 1;
+    let mut tortoise = start.clone();
+    let mut hare = step(start);
+    let mut steps = 1;
+    while !same(&tortoise, &hare) {
+        if steps >= limit {
+            return Err(hare);
+        }
+        let next = step(&hare);
+        if power == period {
+            tortoise = std::mem::replace(&mut hare, next);
+            power *= 2;
+            period = 0;
+        } else {
+            hare = next;
+        }
+        steps += 1;
+        period += 1;
+    }
+
+    // Walk two states a period apart from the beginning; they first agree where the cycle starts.
+    let mut behind = start.clone();
+    let mut ahead = start.clone();
+    for _ in 0..period {
+        ahead = step(&ahead);
+    }
+    let mut first = 0;
+    while !same(&behind, &ahead) {
+        behind = step(&behind);
+        ahead = step(&ahead);
+        first += 1;
+    }
+
+    Ok(Cycle {
+        start: first,
+        period,
+    })
+}
+
+/// Looks for a cycle in the states reached by applying `step` over and over to `start`, where
+/// `same` says whether two states count as the same. Gives up after `limit` steps.
+pub fn find_cycle<S, F, E>(start: &S, limit: u64, mut step: F, mut same: E) -> Option<Cycle>
+where
+    S: Clone,
+    F: FnMut(&S) -> S,
+    E: FnMut(&S, &S) -> bool,
+{
+    if limit == 0 {
+        return None;
+    }
+    brent(start, limit, &mut step, &mut same).ok()
+}
+
+/// The state `n` steps on from `start`, skipping over whole periods once a cycle turns up, along
+/// with the cycle if there was one. States that count as the same have to carry on identically
+/// for the skip to be valid.
+pub fn state_after<S, F, E>(start: S, n: u64, mut step: F, mut same: E) -> (S, Option<Cycle>)
+where
+    S: Clone,
+    F: FnMut(&S) -> S,
+    E: FnMut(&S, &S) -> bool,
+{
+    if n == 0 {
+        return (start, None);
+    }
+
+    match brent(&start, n, &mut step, &mut same) {
+        Err(state) => (state, None),
+        Ok(cycle) => {
+            let mut state = start;
+            for _ in 0..cycle.reduce(n) {
+                state = step(&state);
+            }
+            (state, Some(cycle))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Counts up from 0 to `start`, then loops around `period` states from there.
+    fn rho(start: u64, period: u64) -> impl Fn(&u64) -> u64 {
+        move |&x| {
+            if x + 1 < start + period {
+                x + 1
+            } else {
+                start
+            }
+        }
+    }
+
+    #[test]
+    fn finds_cycles() {
+        for start in 0..20 {
+            for period in 1..20 {
+                assert_eq!(
+                    find_cycle(&0, 1000, rho(start, period), |a, b| a == b),
+                    Some(Cycle { start, period }),
+                    "start {}, period {}",
+                    start,
+                    period
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn fixed_point() {
+        assert_eq!(
+            find_cycle(&5, 1, |&x| x, |a, b| a == b),
+            Some(Cycle {
+                start: 0,
+                period: 1
+            })
+        );
+    }
+
+    #[test]
+    fn gives_up_at_limit() {
+        // Nothing can repeat before the first lap of the cycle is done.
+        assert_eq!(find_cycle(&0, 0, rho(0, 1), |a, b| a == b), None);
+        assert_eq!(find_cycle(&0, 10, rho(4, 7), |a, b| a == b), None);
+        assert_eq!(find_cycle(&0, 1000, |&x| x + 1, |a, b| a == b), None);
+    }
+
+    #[test]
+    fn reduce() {
+        let cycle = Cycle {
+            start: 3,
+            period: 4,
+        };
+        let reduced: Vec<u64> = (0..12).map(|n| cycle.reduce(n)).collect();
+        assert_eq!(reduced, vec![0, 1, 2, 3, 4, 5, 6, 3, 4, 5, 6, 3]);
+        assert_eq!(cycle.reduce(1_000_000_000_003), 3);
+    }
+
+    #[test]
+    fn state_after_matches_stepping() {
+        for &(start, period) in [(0, 1), (0, 5), (3, 1), (6, 4), (25, 13)].iter() {
+            let step = rho(start, period);
+            let mut naive = 0;
+            for n in 0..100 {
+                let (state, _) = state_after(0, n, &step, |a, b| a == b);
+                assert_eq!(state, naive, "start {}, period {}, n {}", start, period, n);
+                naive = step(&naive);
+            }
+        }
+    }
+}